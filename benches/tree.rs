@@ -0,0 +1,125 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+// kubefs is only a binary, pull the arena in directly.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/tree.rs"]
+mod tree;
+
+use tree::{Arena, NodeId};
+
+const SIZES: [u64; 3] = [100, 1_000, 10_000];
+
+/// Builds a tree shaped like a mount: a root with `size / 10` directories of 10 leafs each.
+fn build(size: u64) -> (Arena<String>, NodeId) {
+    let mut arena = Arena::new();
+    let root = arena.add(String::from("root"), None);
+
+    for d in 0..size / 10 {
+        let dir = arena.add(format!("dir-{}", d), Some(root.clone()));
+        for l in 0..10 {
+            arena.add(format!("leaf-{}-{}", d, l), Some(dir.clone()));
+        }
+    }
+
+    (arena, root)
+}
+
+fn bench_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add");
+    for size in SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| build(black_box(size)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_tree_walk_dfs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tree_walk_dfs");
+    for size in SIZES {
+        let (arena, root) = build(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &root, |b, root| {
+            b.iter(|| arena.tree_walk_dfs(black_box(root)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_get_children(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_children");
+    for size in SIZES {
+        let (arena, root) = build(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &root, |b, root| {
+            b.iter(|| arena.get_children(black_box(root)).map(|c| c.len()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_delete_node(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delete_node");
+    for size in SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || {
+                    let (arena, root) = build(size);
+                    let dirs: Vec<NodeId> = arena
+                        .get(&root)
+                        .unwrap()
+                        .children_ids
+                        .iter()
+                        .cloned()
+                        .collect();
+                    (arena, dirs)
+                },
+                |(mut arena, dirs)| {
+                    for dir in dirs.into_iter().step_by(2) {
+                        arena.delete_node(dir);
+                    }
+                    arena
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// The pattern every directory sync goes through: a tenth of the children are replaced.
+fn bench_sync_children(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sync_children");
+    for size in SIZES {
+        let leafs: Vec<String> = (size / 10..size + size / 10)
+            .map(|l| format!("leaf-{}", l))
+            .collect();
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || {
+                    let mut arena = Arena::new();
+                    let root = arena.add(String::from("root"), None);
+                    for l in 0..size {
+                        arena.add(format!("leaf-{}", l), Some(root.clone()));
+                    }
+                    (arena, root, leafs.clone())
+                },
+                |(mut arena, root, leafs)| {
+                    arena.sync_children(&root, leafs, |_| false);
+                    arena
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_add,
+    bench_tree_walk_dfs,
+    bench_get_children,
+    bench_delete_node,
+    bench_sync_children
+);
+criterion_main!(benches);
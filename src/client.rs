@@ -1,41 +1,523 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap, HashSet, VecDeque},
+    env,
+    fmt::Debug,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context;
+use futures::{future::Either, StreamExt, TryStreamExt};
+use hyper::client::HttpConnector;
+use hyper_timeout::TimeoutConnector;
+use k8s_openapi::api::core::v1::{Event as KubeEvent, Namespace, Pod};
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+    CustomResourceColumnDefinition, CustomResourceDefinition, CustomResourceDefinitionVersion,
+};
 use k8s_openapi::apimachinery::pkg::version::Info;
+use k8s_openapi::chrono::{DateTime, Utc};
 use kube::{
-    api::ListParams,
-    core::{DynamicObject, GroupVersionKind, TypeMeta},
-    discovery::{ApiCapabilities, ApiResource},
-    Api, Client, Discovery,
+    api::{DeleteParams, ListParams, LogParams, Patch, PatchParams, PostParams},
+    client::ConfigExt,
+    config::{KubeConfigOptions, Kubeconfig},
+    core::{DynamicObject, GroupVersion, GroupVersionKind, TypeMeta, WatchEvent},
+    discovery::{self, ApiCapabilities, ApiResource},
+    runtime::{
+        reflector::{self, store::Writer, Store},
+        watcher,
+    },
+    Api, Client, Config, ResourceExt,
 };
 use mini_moka::sync::Cache;
-use tokio::runtime::Runtime;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+use tokio::{
+    runtime::{Handle, Runtime},
+    task::JoinHandle,
+};
+use tower::ServiceBuilder;
+use tower_http::trace::TraceLayer;
+use tracing::{debug, debug_span, info, warn};
+
+use crate::options::KubeFsOptions;
+
+/// Number of changes observed by the resource watches that are kept around.
+const RECENT_CHANGES: usize = 100;
+
+/// Number of resources asked for per request when listing.
+const LIST_PAGE_SIZE: u32 = 500;
+
+/// Smallest page a listing that keeps timing out is split into.
+const MIN_LIST_PAGE_SIZE: u32 = 50;
+
+/// Number of times a request that failed on something transient is tried again.
+const MAX_RETRIES: u32 = 3;
+
+/// Backoff before the first retry, doubled for every retry after that.
+const BASE_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Number of resource versions seen by the watches that are kept around to read old versions.
+const RESOURCE_HISTORY: usize = 500;
+
+/// Number of failed requests that are kept around for the `.errors` file.
+const RECENT_ERRORS: usize = 50;
+
+/// How long fetched logs are reused for, long enough for a couple of quick reads in a row.
+const LOG_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Number of containers whose logs are kept around.
+const LOG_CACHE_SIZE: u64 = 32;
+
+/// How long resource counts are reused for, every stat of a lazy kind directory needs one.
+const COUNT_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// How often a request in flight checks whether the process it's made for gave up on it.
+const CALLER_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Mask of SIGKILL in the pending signals of `/proc/<pid>/status`.
+const SIGKILL_MASK: u64 = 1 << (libc::SIGKILL - 1);
+
+/// A request that got cancelled because the process it was made for gave up waiting on it.
+#[derive(Debug)]
+pub struct RequestAbandoned(u32);
+
+impl std::fmt::Display for RequestAbandoned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request abandoned by process {}", self.0)
+    }
+}
+
+impl std::error::Error for RequestAbandoned {}
+
+/// A request to the api server that failed.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub time: DateTime<Utc>,
+    pub message: String,
+}
+
+/// Where the client connects to and how it authenticates, leaving out the credentials
+/// themselves.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub server: String,
+    /// How the client authenticates: exec, auth-provider, token, client-certificate, basic or
+    /// none.
+    pub auth: &'static str,
+    pub impersonate: Option<String>,
+    /// Name the certificate of the api server is checked against, when it isn't the host of
+    /// the server.
+    pub tls_server_name: Option<String>,
+    pub insecure: bool,
+}
+
+impl Connection {
+    fn from(config: &Config) -> Self {
+        let auth = &config.auth_info;
+        let method = if auth.exec.is_some() {
+            "exec"
+        } else if auth.auth_provider.is_some() {
+            "auth-provider"
+        } else if auth.token.is_some() || auth.token_file.is_some() {
+            "token"
+        } else if auth.client_certificate.is_some() || auth.client_certificate_data.is_some() {
+            "client-certificate"
+        } else if auth.username.is_some() && auth.password.is_some() {
+            "basic"
+        } else {
+            "none"
+        };
+
+        Connection {
+            server: config.cluster_url.to_string(),
+            auth: method,
+            impersonate: auth.impersonate.clone(),
+            tls_server_name: config.tls_server_name.clone(),
+            insecure: config.accept_invalid_certs,
+        }
+    }
+}
+
+/// An add or update of a resource observed by a watch.
+#[derive(Debug, Clone)]
+pub struct ResourceChange {
+    pub time: SystemTime,
+    pub namespace: String,
+    /// Api group of the resource, empty for the core group.
+    pub group: String,
+    pub plural: String,
+    pub name: String,
+}
+
+/// Logs fetched for a container, along with what they were fetched for.
+#[derive(Debug, Clone)]
+struct CachedLogs {
+    /// Id of the container the logs came from, it changes when the container restarts.
+    container_id: String,
+    /// The request the logs were fetched with. `LogParams` has no `PartialEq`, so its debug
+    /// output is what tells requests apart.
+    params: String,
+    logs: Vec<u8>,
+}
+
+/// Listing of a kind across every namespace, kept up to date by a watch running in the
+/// background.
+struct Informer {
+    store: Store<DynamicObject>,
+    task: JoinHandle<()>,
+}
+
+type Informers = Arc<Mutex<HashMap<String, Informer>>>;
+
+/// A listing browsed with `-o prefetch`, how often and when it was last fetched.
+struct Visited {
+    namespace: String,
+    resource: ApiResource,
+    visits: u64,
+    fetched: Instant,
+}
+
+type Prefetch = Arc<Mutex<HashMap<String, Visited>>>;
 
 pub struct KubeClient {
     runtime: Runtime,
-    client: Client,
+    options: KubeFsOptions,
+    client: Arc<RwLock<Client>>,
+    connection: Arc<RwLock<Option<Connection>>>,
+    context: Arc<RwLock<Option<String>>>,
+    kubeconfig_watcher: Option<RecommendedWatcher>,
+    connect_timeout: Option<Duration>,
+    max_backoff: Duration,
+    list_timeout: Option<Duration>,
 
     cache: Cache<String, Vec<DynamicObject>>, // cache: Cell<HashMap<String, Vec<DynamicObject>>>,
+    crd_versions: Cache<String, Option<CustomResourceDefinitionVersion>>,
+    /// OpenAPI documents per group version, see `get_openapi_document`.
+    openapi: Cache<String, Arc<serde_json::Value>>,
+    logs: Cache<String, CachedLogs>,
+    /// Resource counts per namespace and kind, see `count_resources`.
+    counts: Cache<String, u64>,
+    no_cache: bool,
+    informers: Option<Informers>,
+    prefetch: Option<Prefetch>,
+    /// Watch evicting the namespace listing, see `watch_namespaces`.
+    namespace_watch: Arc<Mutex<Option<JoinHandle<()>>>>,
+
+    watch: bool,
+    watched: Arc<Mutex<HashSet<String>>>,
+    /// Latest resourceVersion seen per watched listing, the watch resumes from it.
+    resource_versions: Arc<Mutex<HashMap<String, String>>>,
+    changes: Arc<Mutex<VecDeque<ResourceChange>>>,
+    history: Arc<Mutex<VecDeque<(String, DynamicObject)>>>,
+    errors: Mutex<VecDeque<ApiError>>,
+    /// Process the current filesystem operation is done for, see `set_caller`.
+    caller: Mutex<Option<u32>>,
 }
 
 impl KubeClient {
-    pub fn new() -> Self {
-        let runtime = tokio::runtime::Runtime::new().expect("Unable to create a runtime");
-        let client = runtime
-            .block_on(async { Client::try_default().await })
-            .unwrap();
-        KubeClient {
+    pub fn new(options: &KubeFsOptions) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Runtime::new().context("Unable to create a runtime")?;
+        let (client, connection) = runtime.block_on(build_client(options))?;
+
+        let kube_client = KubeClient::with_client(client, runtime, options)?;
+        *kube_client.connection.write().unwrap() = Some(connection);
+
+        Ok(kube_client)
+    }
+
+    /// Wraps an already built `Client`, e.g. one backed by a mock service in tests. The client
+    /// has to be created within `runtime`, as its requests are driven by it.
+    pub fn with_client(
+        client: Client,
+        runtime: Runtime,
+        options: &KubeFsOptions,
+    ) -> anyhow::Result<Self> {
+        let mut cache = Cache::builder();
+        if let Some(ttl) = options.cache_ttl {
+            cache = cache.time_to_live(ttl);
+        }
+        if let Some(size) = options.cache_size {
+            cache = cache.max_capacity(size);
+        }
+
+        let mut kube_client = KubeClient {
             runtime,
-            client,
-            cache: Cache::builder().build(),
+            options: options.clone(),
+            client: Arc::new(RwLock::new(client)),
+            connection: Arc::new(RwLock::new(None)),
+            context: Arc::new(RwLock::new(read_context(options))),
+            kubeconfig_watcher: None,
+            connect_timeout: options.connect_timeout,
+            max_backoff: options.max_backoff,
+            list_timeout: options.list_timeout,
+            cache: cache.build(),
+            crd_versions: Cache::builder().build(),
+            openapi: Cache::builder().build(),
+            logs: Cache::builder()
+                .time_to_live(LOG_CACHE_TTL)
+                .max_capacity(LOG_CACHE_SIZE)
+                .build(),
+            counts: Cache::builder().time_to_live(COUNT_CACHE_TTL).build(),
+            no_cache: options.no_cache,
+            informers: options
+                .informers
+                .then(|| Arc::new(Mutex::new(HashMap::new()))),
+            prefetch: options
+                .prefetch
+                .filter(|_| options.cache_ttl.is_some() && !options.no_cache && !options.watch)
+                .map(|_| Arc::new(Mutex::new(HashMap::new()))),
+            namespace_watch: Arc::new(Mutex::new(None)),
+            watch: options.watch,
+            watched: Arc::new(Mutex::new(HashSet::new())),
+            resource_versions: Arc::new(Mutex::new(HashMap::new())),
+            changes: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_CHANGES))),
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(RESOURCE_HISTORY))),
+            errors: Mutex::new(VecDeque::with_capacity(RECENT_ERRORS)),
+            caller: Mutex::new(None),
+        };
+
+        if let Some(interval) = options
+            .resync
+            .filter(|_| options.watch && !options.no_cache)
+        {
+            kube_client.start_resync(interval);
+        }
+
+        // Without a ttl nothing expires, and prefetching every `ttl / 4` would never sleep.
+        let ttl = options.cache_ttl.filter(|ttl| !ttl.is_zero());
+        if let (Some(ttl), Some(listings)) = (ttl, options.prefetch) {
+            kube_client.start_prefetch(ttl, listings);
+        }
+
+        if options.reload_kubeconfig && options.server.is_none() {
+            kube_client.watch_kubeconfig(options)?;
+        }
+
+        Ok(kube_client)
+    }
+
+    /// With `-o prefetch` the most browsed listings get fetched again in the background shortly
+    /// before they would expire, so browsing them rarely has to wait on the api server.
+    fn start_prefetch(&self, ttl: Duration, listings: usize) {
+        let prefetch = match &self.prefetch {
+            Some(prefetch) => prefetch.clone(),
+            None => return,
+        };
+        let cache = self.cache.clone();
+        let client = self.client.clone();
+        let params = self.list_params();
+        // Leaves a quarter of the ttl for the listing to come back.
+        let refresh_after = ttl.mul_f64(0.75);
+
+        self.runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(ttl / 4).await;
+
+                let due: Vec<(String, String, ApiResource)> = {
+                    let prefetch = prefetch.lock().unwrap();
+                    let mut visited: Vec<(&String, &Visited)> = prefetch.iter().collect();
+                    visited.sort_by_key(|(_, v)| std::cmp::Reverse(v.visits));
+                    visited
+                        .into_iter()
+                        .take(listings)
+                        .filter(|(_, v)| v.fetched.elapsed() >= refresh_after)
+                        .map(|(key, v)| (key.clone(), v.namespace.clone(), v.resource.clone()))
+                        .collect()
+                };
+
+                for (key, namespace, resource) in due {
+                    let client = client.read().unwrap().clone();
+                    let api: Api<DynamicObject> =
+                        Api::namespaced_with(client, &namespace, &resource);
+                    match list_paged(&api, params.clone()).await {
+                        Ok(objs) => {
+                            cache.insert(key.clone(), objs);
+                            if let Some(visited) = prefetch.lock().unwrap().get_mut(&key) {
+                                visited.fetched = Instant::now();
+                            }
+                        }
+                        // Browsing the listing fetches it again, and reports what went wrong.
+                        Err(err) => debug!("Prefetching {} failed: {}", key, err),
+                    }
+                }
+            }
+        });
+    }
+
+    /// Counts a visit of a listing for `-o prefetch`, `fetched` when it had to be listed.
+    fn visited(&self, key: &str, namespace: &str, resource: &ApiResource, fetched: bool) {
+        let prefetch = match &self.prefetch {
+            Some(prefetch) => prefetch,
+            None => return,
+        };
+
+        let mut prefetch = prefetch.lock().unwrap();
+        let visited = prefetch.entry(key.into()).or_insert_with(|| Visited {
+            namespace: namespace.into(),
+            resource: resource.clone(),
+            visits: 0,
+            fetched: Instant::now(),
+        });
+        visited.visits += 1;
+        if fetched {
+            visited.fetched = Instant::now();
         }
     }
 
+    /// With `-o resync=<duration>` the listings of watched resources get evicted periodically,
+    /// so they are listed again in full and drift from missed watch events heals itself.
+    fn start_resync(&self, interval: Duration) {
+        let cache = self.cache.clone();
+        let watched = self.watched.clone();
+
+        self.runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+
+                cache.invalidate(&String::from("namespaces"));
+                for key in watched.lock().unwrap().iter() {
+                    cache.invalidate(key);
+                }
+            }
+        });
+    }
+
     pub fn cluster_info(&self) -> anyhow::Result<Info> {
         let info = self
             .runtime
-            .block_on(async { self.client.apiserver_version().await })?;
+            .block_on(async { self.client().apiserver_version().await });
+        let info = self
+            .record("getting the api server version", info)
+            .map_err(|err| match self.connect_timeout {
+                Some(timeout) if is_timeout(&err) => anyhow::Error::msg(format!(
+                    "Could not connect to the api server within {}s",
+                    timeout.as_secs()
+                )),
+                _ => err.into(),
+            })?;
 
         Ok(info)
     }
 
+    /// Namespace set on the current context of the kubeconfig, `default` when it has none.
+    pub fn default_namespace(&self) -> String {
+        self.client().default_namespace().to_string()
+    }
+
+    /// Where the client connects to and how, `None` for clients that weren't built from a
+    /// configuration.
+    pub fn connection(&self) -> Option<Connection> {
+        self.connection.read().unwrap().clone()
+    }
+
+    /// Name of the current context in the kubeconfig, if there is one.
+    pub fn current_context(&self) -> Option<String> {
+        self.context.read().unwrap().clone()
+    }
+
+    /// Client to send requests with. Requests in flight hold on to their own clone, so swapping
+    /// the client after a kubeconfig change doesn't affect them.
+    fn client(&self) -> Client {
+        self.client.read().unwrap().clone()
+    }
+
+    /// With `-o reload_kubeconfig` the kubeconfig gets watched, and every change to it rebuilds
+    /// the client and drops the cached responses. Watches that are already running keep using
+    /// the client they were started with.
+    fn watch_kubeconfig(&mut self, options: &KubeFsOptions) -> anyhow::Result<()> {
+        let path = kubeconfig_path().ok_or(anyhow::Error::msg("Unable to locate a kubeconfig"))?;
+        let directory = path
+            .parent()
+            .ok_or(anyhow::Error::msg("Kubeconfig has no parent directory"))?
+            .to_path_buf();
+
+        let client = Arc::clone(&self.client);
+        let connection = self.connection.clone();
+        let context = self.context.clone();
+        let cache = self.cache.clone();
+        let crd_versions = self.crd_versions.clone();
+        let openapi = self.openapi.clone();
+        let logs = self.logs.clone();
+        let counts = self.counts.clone();
+        let informers = self.informers.clone();
+        let namespace_watch = self.namespace_watch.clone();
+        let params = self.list_params();
+        let handle = self.runtime.handle().clone();
+        let options = options.clone();
+
+        // Tools tend to replace the kubeconfig rather than write to it, so the directory is
+        // watched instead of the file itself.
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let changed = match event {
+                Ok(event) => event.paths.contains(&path) && !event.kind.is_access(),
+                Err(_) => false,
+            };
+            if !changed {
+                return;
+            }
+
+            match handle.block_on(build_client(&options)) {
+                Ok((new_client, new_connection)) => {
+                    info!("Kubeconfig changed, reconnecting");
+                    *client.write().unwrap() = new_client.clone();
+                    *connection.write().unwrap() = Some(new_connection);
+                    *context.write().unwrap() = read_context(&options);
+                    restart_namespace_watch(
+                        &namespace_watch,
+                        &handle,
+                        new_client,
+                        cache.clone(),
+                        params.clone(),
+                    );
+                    cache.invalidate_all();
+                    crd_versions.invalidate_all();
+                    openapi.invalidate_all();
+                    logs.invalidate_all();
+                    counts.invalidate_all();
+                    stop_informers(&informers);
+                }
+                Err(err) => warn!("Kubeconfig changed, but unable to reconnect: {:#}", err),
+            }
+        })?;
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+        self.kubeconfig_watcher = Some(watcher);
+        Ok(())
+    }
+
+    /// Rebuilds the client from the kubeconfig and drops every cached response, e.g. after a
+    /// SIGHUP. The namespace watch moves over to the new client, other watches that are already
+    /// running keep using the client they were started with.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let (client, connection) = self
+            .runtime
+            .block_on(build_client(&self.options))
+            .context("Unable to reconnect")?;
+
+        *self.client.write().unwrap() = client.clone();
+        *self.connection.write().unwrap() = Some(connection);
+        *self.context.write().unwrap() = read_context(&self.options);
+        restart_namespace_watch(
+            &self.namespace_watch,
+            self.runtime.handle(),
+            client,
+            self.cache.clone(),
+            self.list_params(),
+        );
+        self.cache.invalidate_all();
+        self.crd_versions.invalidate_all();
+        self.openapi.invalidate_all();
+        self.logs.invalidate_all();
+        self.counts.invalidate_all();
+        stop_informers(&self.informers);
+
+        Ok(())
+    }
+
     pub fn discover_api_resources(
         &self,
         filter: Option<&Vec<&str>>,
@@ -43,55 +525,129 @@ impl KubeClient {
     ) -> anyhow::Result<Vec<(ApiResource, ApiCapabilities)>> {
         let default = Vec::new();
         let filter = filter.unwrap_or(&default);
-        let discovery = self.runtime.block_on(async {
-            let discovery = Discovery::new(self.client.clone())
-                .filter(filter)
-                .run()
-                .await;
-            discovery
-        })?;
+        let client = self.client();
+
+        // Every group is discovered on its own, so a single broken aggregated api service (e.g. a
+        // metrics-server that is down) only hides its own resources instead of failing the mount.
+        let versions = self.runtime.block_on(async {
+            let mut versions = Vec::new();
+            if filter.is_empty() || filter.contains(&"") {
+                versions.push(GroupVersion::gv("", "v1"));
+            }
+
+            if filter.is_empty() || filter.iter().any(|f| !f.is_empty()) {
+                for group in client.list_api_groups().await?.groups {
+                    if !filter.is_empty() && !filter.contains(&group.name.as_str()) {
+                        continue;
+                    }
+
+                    let version = group
+                        .preferred_version
+                        .or_else(|| group.versions.into_iter().next());
+                    if let Some(version) = version {
+                        versions.push(GroupVersion::gv(&group.name, &version.version));
+                    }
+                }
+            }
+
+            Ok::<_, kube::Error>(versions)
+        });
+        let versions = self.record("discovering api groups", versions)?;
+
+        let groups = self.runtime.block_on(futures::future::join_all(
+            versions
+                .iter()
+                .map(|gv| discovery::pinned_group(&client, gv)),
+        ));
 
         let operations = operations.unwrap_or(&default);
 
-        Ok(discovery
-            .groups()
+        Ok(versions
+            .iter()
+            .zip(groups)
+            .filter_map(|(gv, group)| {
+                let action = format!("discovering {}", gv.api_version());
+                match self.record(&action, group) {
+                    Ok(group) => Some(group),
+                    Err(e) => {
+                        warn!("Skipping {}: {:#}", gv.api_version(), e);
+                        None
+                    }
+                }
+            })
             .flat_map(|g| g.recommended_resources())
             .filter(|(_, c)| operations.iter().all(|o| c.supports_operation(o)))
             .collect())
     }
 
+    /// The short names of a kind, e.g. `po` for pods. Discovery drops these, so they are looked
+    /// up in the resource list of the group version on every call.
+    pub fn get_short_names(&self, resource: &ApiResource) -> anyhow::Result<Vec<String>> {
+        let client = self.client();
+        let list = self.runtime.block_on(async {
+            match resource.group.as_str() {
+                "" => client.list_core_api_resources(&resource.version).await,
+                _ => client.list_api_group_resources(&resource.api_version).await,
+            }
+        });
+        let list = self.record(format!("discovering {}", resource.api_version), list)?;
+
+        Ok(list
+            .resources
+            .into_iter()
+            .find(|r| r.name == resource.plural)
+            .and_then(|r| r.short_names)
+            .unwrap_or_default())
+    }
+
     pub fn list_namespaces(&self) -> anyhow::Result<Vec<DynamicObject>> {
         let key: String = "namespaces".into();
-        if let Some(ns) = self.cache.get(&key) {
+        if let Some(ns) = self.cached(&key) {
             return Ok(ns);
         }
 
-        self.runtime.block_on(async {
-            let resource = ApiResource::from_gvk(&GroupVersionKind {
-                group: String::from(""),
-                version: String::from("v1"),
-                kind: String::from("Namespace"),
-            });
-            let namespace: Api<DynamicObject> = Api::all_with(self.client.clone(), &resource);
+        let resource = &ApiResource::from_gvk(&GroupVersionKind {
+            group: String::from(""),
+            version: String::from("v1"),
+            kind: String::from("Namespace"),
+        });
 
-            let all = namespace.list(&ListParams::default()).await?;
+        let all = self.retry(move || async move {
+            let namespace: Api<DynamicObject> = Api::all_with(self.client(), resource);
+            list_paged(&namespace, self.list_params()).await
+        });
+        let all = self.record("listing namespaces", all)?;
 
-            let namespaces: Vec<DynamicObject> = all
-                .items
-                .iter()
-                .map(|n| {
-                    let mut obj = n.clone();
-                    obj.types = Some(TypeMeta {
-                        api_version: resource.version.clone(),
-                        kind: resource.kind.clone(),
-                    });
-                    obj
-                })
-                .collect();
+        let namespaces: Vec<DynamicObject> = all
+            .iter()
+            .map(|n| {
+                let mut obj = n.clone();
+                obj.types = Some(TypeMeta {
+                    api_version: resource.version.clone(),
+                    kind: resource.kind.clone(),
+                });
+                obj
+            })
+            .collect();
 
-            self.cache.insert(key, namespaces.clone());
-            Ok(namespaces)
-        })
+        self.store(key, namespaces.clone());
+        Ok(namespaces)
+    }
+
+    /// Watches namespaces in the background and evicts the cached namespace listing whenever one
+    /// gets added, changed or deleted, so the top level of the tree doesn't wait on a stale cache.
+    /// The watch follows the client along when the context changes.
+    pub fn watch_namespaces(&self) {
+        if self.no_cache {
+            return;
+        }
+
+        *self.namespace_watch.lock().unwrap() = Some(spawn_namespace_watch(
+            self.runtime.handle(),
+            self.client(),
+            self.cache.clone(),
+            self.list_params(),
+        ));
     }
 
     pub fn list_resources(
@@ -101,20 +657,1911 @@ impl KubeClient {
     ) -> anyhow::Result<Vec<DynamicObject>> {
         let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
 
-        if let Some(objs) = self.cache.get(&key) {
+        if let Some(objs) = self.cached(&key) {
+            self.visited(&key, namespace, resource, false);
             return Ok(objs);
         }
+        self.visited(&key, namespace, resource, true);
 
-        let resources = self.runtime.block_on(async {
-            let api: Api<DynamicObject> =
-                Api::namespaced_with(self.client.clone(), namespace, resource);
-            api.list(&ListParams::default()).await
-        })?;
-
-        let objs: Vec<DynamicObject> = resources.items.iter().map(|p| p.clone()).collect();
+        let resources = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+            list_paged_versioned(&api, self.list_params()).await
+        });
+        let (objs, version) = self.record(
+            format!("listing {} in {}", resource.plural, namespace),
+            resources,
+        )?;
 
-        self.cache.insert(key, objs.clone());
+        if let Some(version) = version.filter(|_| self.watch) {
+            self.resource_versions
+                .lock()
+                .unwrap()
+                .insert(key.clone(), version);
+        }
+        self.store(key.clone(), objs.clone());
+        self.watch_resources(key, namespace, resource);
 
         Ok(objs)
     }
+
+    /// Lists a kind across every namespace. With `-o informers` the first listing seeds a store
+    /// that a watch keeps up to date from then on, and later listings are served from that store
+    /// instead of going to the api server. `None` when informers aren't enabled.
+    pub fn list_all_resources(
+        &self,
+        resource: &ApiResource,
+    ) -> Option<anyhow::Result<Vec<DynamicObject>>> {
+        let informers = self.informers.as_ref()?;
+        let key = format!("{}/{}", resource.api_version, resource.kind);
+
+        if let Some(informer) = informers.lock().unwrap().get(&key) {
+            let objs = informer.store.state();
+            return Some(Ok(objs.iter().map(|o| (**o).clone()).collect()));
+        }
+
+        let resources = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::all_with(self.client(), resource);
+            list_paged(&api, self.list_params()).await
+        });
+        let objs = match self.record(format!("listing {}", resource.plural), resources) {
+            Ok(objs) => objs,
+            Err(err) => return Some(Err(err.into())),
+        };
+
+        // Seeding the store with the listing makes it usable right away, rather than only once
+        // the watcher finished listing everything itself.
+        let mut writer = Writer::new(resource.clone());
+        writer.apply_watcher_event(&watcher::Event::Restarted(objs.clone()));
+        let store = writer.as_reader();
+
+        let api: Api<DynamicObject> = Api::all_with(self.client(), resource);
+        let params = self.list_params();
+        let plural = resource.plural.clone();
+        let task = self.runtime.spawn(async move {
+            let mut events = reflector::reflector(writer, watcher(api, params)).boxed();
+
+            while let Some(event) = events.next().await {
+                if let Err(err) = event {
+                    warn!("Informer for {} failed: {}", plural, err);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        });
+
+        informers
+            .lock()
+            .unwrap()
+            .insert(key, Informer { store, task });
+
+        Some(Ok(objs))
+    }
+
+    /// With `-o watch` every listed resource gets watched, keeping its cache entry up to date
+    /// and recording the changes that come in. Watches ask for bookmarks and pick up from the
+    /// last version they saw when they reconnect, only listing everything again when the api
+    /// server no longer has that version (410 Gone).
+    fn watch_resources(&self, key: String, namespace: &str, resource: &ApiResource) {
+        if !self.watch || self.no_cache || !self.watched.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+
+        let cache = self.cache.clone();
+        let changes = self.changes.clone();
+        let history = self.history.clone();
+        let group = resource.group.clone();
+        let plural = resource.plural.clone();
+        let versions = self.resource_versions.clone();
+        let params = self.list_params();
+        let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+        let max_backoff = self.max_backoff;
+
+        self.runtime.spawn(async move {
+            // Times in a row the watch failed without applying a single event.
+            let mut failures = 0;
+
+            loop {
+                let version = versions.lock().unwrap().get(&key).cloned();
+                let version = match version {
+                    Some(version) => version,
+                    // Nothing to resume from, or it got too old, so everything is listed again.
+                    None => match list_paged_versioned(&api, params.clone()).await {
+                        Ok((objs, version)) => {
+                            cache.insert(key.clone(), objs);
+                            version.unwrap_or_default()
+                        }
+                        Err(err) => {
+                            warn!("Relisting {} failed: {}", key, err);
+                            tokio::time::sleep(Duration::from_secs(5)).await;
+                            continue;
+                        }
+                    },
+                };
+
+                let mut events = match api.watch(&params, &version).await {
+                    Ok(events) => events.boxed(),
+                    Err(kube::Error::Api(err)) if err.code == 410 => {
+                        versions.lock().unwrap().remove(&key);
+                        continue;
+                    }
+                    Err(err) => {
+                        warn!("Watch for {} failed: {}", key, err);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        continue;
+                    }
+                };
+
+                // The stream ends when the server times the watch out, after which it's resumed
+                // from the last version seen rather than listing everything again.
+                while let Some(event) = events.next().await {
+                    if matches!(
+                        event,
+                        Ok(WatchEvent::Added(_))
+                            | Ok(WatchEvent::Modified(_))
+                            | Ok(WatchEvent::Deleted(_))
+                            | Ok(WatchEvent::Bookmark(_))
+                    ) {
+                        failures = 0;
+                    }
+
+                    match event {
+                        Ok(WatchEvent::Added(obj)) | Ok(WatchEvent::Modified(obj)) => {
+                            if let Some(version) = obj.resource_version() {
+                                versions.lock().unwrap().insert(key.clone(), version);
+                            }
+
+                            // An evicted listing is left for the next read to list again in full.
+                            if let Some(mut objs) = cache.get(&key) {
+                                objs.retain(|o| o.name_any() != obj.name_any());
+                                objs.push(obj.clone());
+                                cache.insert(key.clone(), objs);
+                            }
+
+                            let mut history = history.lock().unwrap();
+                            if history.len() == RESOURCE_HISTORY {
+                                history.pop_back();
+                            }
+                            history.push_front((key.clone(), obj.clone()));
+
+                            let mut changes = changes.lock().unwrap();
+                            if changes.len() == RECENT_CHANGES {
+                                changes.pop_back();
+                            }
+                            changes.push_front(ResourceChange {
+                                time: SystemTime::now(),
+                                namespace: obj.namespace().unwrap_or_default(),
+                                group: group.clone(),
+                                plural: plural.clone(),
+                                name: obj.name_any(),
+                            });
+                        }
+                        Ok(WatchEvent::Deleted(obj)) => {
+                            if let Some(version) = obj.resource_version() {
+                                versions.lock().unwrap().insert(key.clone(), version);
+                            }
+
+                            if let Some(mut objs) = cache.get(&key) {
+                                objs.retain(|o| o.name_any() != obj.name_any());
+                                cache.insert(key.clone(), objs);
+                            }
+                        }
+                        Ok(WatchEvent::Bookmark(bookmark)) => {
+                            versions
+                                .lock()
+                                .unwrap()
+                                .insert(key.clone(), bookmark.metadata.resource_version);
+                        }
+                        // The version is older than what the api server still keeps around.
+                        Ok(WatchEvent::Error(err)) if err.code == 410 => {
+                            versions.lock().unwrap().remove(&key);
+                            break;
+                        }
+                        Ok(WatchEvent::Error(err)) => {
+                            warn!("Watch for {} failed: {}", key, err);
+                            failures += 1;
+                            break;
+                        }
+                        // Load balancers and proxies cut long lived connections, which breaks
+                        // the stream off. Resuming from the last version seen doesn't miss a
+                        // thing.
+                        Err(err) => {
+                            warn!("Watch for {} broke off, resuming: {}", key, err);
+                            failures += 1;
+                            break;
+                        }
+                    }
+                }
+
+                // A watch that broke off is resumed straight away. One that keeps failing
+                // without getting anything done, like on an error the server sends every time,
+                // backs off rather than hammering the api server.
+                if failures > 1 {
+                    tokio::time::sleep(watch_backoff(failures - 2, max_backoff)).await;
+                }
+            }
+        });
+    }
+
+    /// Changes observed by the resource watches, newest first.
+    pub fn recent_changes(&self) -> Vec<ResourceChange> {
+        self.changes.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Number of objects in the cached listing of a kind in a namespace, `None` when there is no
+    /// cached listing.
+    pub fn cached_count(&self, namespace: &str, resource: &ApiResource) -> Option<usize> {
+        let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+        self.cached(&key).map(|objs| objs.len())
+    }
+
+    /// How long listings are cached for, `None` when they stay until evicted. Listings aren't
+    /// cached at all with `-o no_cache`.
+    pub fn cache_ttl(&self) -> Option<Duration> {
+        self.options.cache_ttl
+    }
+
+    pub fn is_caching(&self) -> bool {
+        !self.no_cache
+    }
+
+    /// Drops every cached response, so everything is fetched fresh on the next read.
+    pub fn clear_cache(&self) {
+        self.cache.invalidate_all();
+        self.crd_versions.invalidate_all();
+        self.openapi.invalidate_all();
+        self.logs.invalidate_all();
+        self.counts.invalidate_all();
+    }
+
+    /// Failed requests, newest first.
+    pub fn recent_errors(&self) -> Vec<ApiError> {
+        self.errors.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Parameters for list and watch calls, with the timeout of `-o list_timeout`.
+    fn list_params(&self) -> ListParams {
+        match self.list_timeout {
+            Some(timeout) => ListParams::default().timeout(timeout.as_secs() as u32),
+            None => ListParams::default(),
+        }
+    }
+
+    /// Sets the process the requests that follow are made for. Requests made for a process
+    /// get cancelled once it gives up waiting on them, `None` lets them run to completion.
+    pub fn set_caller(&self, pid: Option<u32>) {
+        *self.caller.lock().unwrap() = pid;
+    }
+
+    /// Runs `request` until it completes or its caller gives up on it. fuser 0.12 answers FUSE
+    /// interrupts itself, so a Ctrl-C on a slow `ls` never reaches us. The kernel doesn't let the
+    /// killed process exit before it gets its reply though, which leaves SIGKILL pending on it
+    /// for as long as the request runs.
+    async fn abandonable<T>(
+        &self,
+        request: impl Future<Output = Result<T, kube::Error>>,
+    ) -> Result<T, kube::Error> {
+        // Copied out, so the lock isn't held while the request runs.
+        let caller = *self.caller.lock().unwrap();
+        let pid = match caller {
+            Some(pid) => pid,
+            None => return request.await,
+        };
+
+        let abandoned = async move {
+            while !is_killed(pid) {
+                tokio::time::sleep(CALLER_CHECK_INTERVAL).await;
+            }
+        };
+
+        futures::pin_mut!(request, abandoned);
+        match futures::future::select(request, abandoned).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => {
+                warn!("Process {} was killed, cancelling its request", pid);
+                Err(kube::Error::Service(Box::new(RequestAbandoned(pid))))
+            }
+        }
+    }
+
+    /// Runs a request, retrying it a couple of times when it fails on something that is likely
+    /// to be transient: connection problems, throttling or a server error.
+    fn retry<T, F, Fut>(&self, request: F) -> Result<T, kube::Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, kube::Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.runtime.block_on(self.abandonable(request())) {
+                Err(err) if attempt < MAX_RETRIES && is_transient(&err) => {
+                    let backoff = self.backoff(attempt);
+                    warn!("Request failed ({}), retrying in {:?}", err, backoff);
+                    std::thread::sleep(backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Exponential backoff with full jitter: a random delay up to the exponential backoff, so
+    /// mounts sharing a cluster don't all retry in lockstep after an api server blip.
+    fn backoff(&self, attempt: u32) -> Duration {
+        jitter((BASE_BACKOFF * 2u32.pow(attempt)).min(self.max_backoff))
+    }
+
+    /// Keeps track of the request when it failed, so it shows up in the `.errors` file.
+    fn record<T, E: std::fmt::Display>(
+        &self,
+        action: impl std::fmt::Display,
+        result: Result<T, E>,
+    ) -> Result<T, E> {
+        if let Err(err) = &result {
+            let mut errors = self.errors.lock().unwrap();
+            if errors.len() == RECENT_ERRORS {
+                errors.pop_back();
+            }
+            errors.push_front(ApiError {
+                time: Utc::now(),
+                message: format!("{}: {}", action, err),
+            });
+        }
+
+        result
+    }
+
+    /// Patches a single namespaced object and updates the cached listing it's part of with the
+    /// result, so the change is visible straight away.
+    pub fn patch_resource(
+        &self,
+        namespace: &str,
+        resource: &ApiResource,
+        name: &str,
+        patch: &Patch<serde_json::Value>,
+    ) -> anyhow::Result<DynamicObject> {
+        let obj = self.runtime.block_on(async {
+            let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+            let params = PatchParams {
+                field_manager: Some(self.options.field_manager.clone()),
+                ..PatchParams::default()
+            };
+            api.patch(name, &params, patch).await
+        });
+        let obj = self
+            .record(
+                format!("patching {} {} in {}", resource.plural, name, namespace),
+                obj,
+            )
+            .map_err(rejected_write)?;
+
+        let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+        if let Some(mut objs) = self.cached(&key) {
+            objs.retain(|o| o.name_any() != name);
+            objs.push(obj.clone());
+            self.store(key, objs);
+        }
+
+        Ok(obj)
+    }
+
+    /// Server side applies an object, like `kubectl apply --server-side`. Cluster scoped objects
+    /// are applied without a namespace.
+    ///
+    /// A manifest carrying a resourceVersion conflicts once the object changed since, which is
+    /// bound to happen for objects a controller keeps reconciling. Those conflicts are retried
+    /// up to `write_retries` times on the current resourceVersion, applying the same fields. A
+    /// conflict over fields another manager owns doesn't go away by retrying, so it isn't.
+    pub fn apply_resource(
+        &self,
+        namespace: Option<&str>,
+        resource: &ApiResource,
+        name: &str,
+        obj: &DynamicObject,
+    ) -> anyhow::Result<DynamicObject> {
+        let api: Api<DynamicObject> = match namespace {
+            Some(namespace) => Api::namespaced_with(self.client(), namespace, resource),
+            None => Api::all_with(self.client(), resource),
+        };
+
+        let mut obj = obj.clone();
+        let mut attempt = 0;
+        let applied = loop {
+            let applied = self.runtime.block_on(api.patch(
+                name,
+                &PatchParams::apply(&self.options.field_manager),
+                &Patch::Apply(&obj),
+            ));
+
+            match applied {
+                Err(kube::Error::Api(response))
+                    if response.code == 409
+                        && obj.metadata.resource_version.is_some()
+                        && attempt < self.options.write_retries =>
+                {
+                    attempt += 1;
+                    warn!(
+                        "Conflict applying {} {}, retrying on the current version",
+                        resource.plural, name
+                    );
+                    let current = self.runtime.block_on(api.get_opt(name));
+                    let current =
+                        self.record(format!("getting {} {}", resource.plural, name), current)?;
+                    obj.metadata.resource_version =
+                        current.and_then(|c| c.metadata.resource_version);
+                }
+                applied => break applied,
+            }
+        };
+        let applied = self
+            .record(format!("applying {} {}", resource.plural, name), applied)
+            .map_err(rejected_write)?;
+
+        if let Some(namespace) = namespace {
+            let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+            if let Some(mut objs) = self.cached(&key) {
+                objs.retain(|o| o.name_any() != name);
+                objs.push(applied.clone());
+                self.store(key, objs);
+            }
+        }
+
+        Ok(applied)
+    }
+
+    /// Deletes a namespace, the api server deletes everything in it along with it.
+    pub fn delete_namespace(&self, name: &str) -> anyhow::Result<()> {
+        let deleted = self.retry(move || async move {
+            let api: Api<Namespace> = Api::all(self.client());
+            api.delete(name, &DeleteParams::default()).await
+        });
+        self.record(format!("deleting namespace {}", name), deleted)?;
+
+        self.cache.invalidate(&String::from("namespaces"));
+        Ok(())
+    }
+
+    /// Creates a namespaced object and adds it to the cached listing of its kind.
+    pub fn create_resource(
+        &self,
+        namespace: &str,
+        resource: &ApiResource,
+        obj: &DynamicObject,
+    ) -> anyhow::Result<DynamicObject> {
+        let created = self.runtime.block_on(async {
+            let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+            let params = PostParams {
+                field_manager: Some(self.options.field_manager.clone()),
+                ..PostParams::default()
+            };
+            api.create(&params, obj).await
+        });
+        let created = self
+            .record(
+                format!(
+                    "creating {} {} in {}",
+                    resource.plural,
+                    obj.name_any(),
+                    namespace
+                ),
+                created,
+            )
+            .map_err(rejected_write)?;
+
+        let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+        if let Some(mut objs) = self.cached(&key) {
+            objs.push(created.clone());
+            self.store(key, objs);
+        }
+
+        Ok(created)
+    }
+
+    /// Number of resources of a kind in a namespace. Asks for a single item and relies on the
+    /// `remainingItemCount` the api server reports, unless the full listing is cached already.
+    /// Counts are kept for `COUNT_CACHE_TTL`.
+    pub fn count_resources(&self, namespace: &str, resource: &ApiResource) -> anyhow::Result<u64> {
+        let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+
+        if let Some(objs) = self.cached(&key) {
+            return Ok(objs.len() as u64);
+        }
+        if let Some(count) = self.counts.get(&key).filter(|_| !self.no_cache) {
+            return Ok(count);
+        }
+
+        let list = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+            api.list(&ListParams::default().limit(1)).await
+        });
+        let list = self.record(
+            format!("counting {} in {}", resource.plural, namespace),
+            list,
+        )?;
+
+        let remaining = list.metadata.remaining_item_count.unwrap_or(0).max(0) as u64;
+        let count = list.items.len() as u64 + remaining;
+        if !self.no_cache {
+            self.counts.insert(key, count);
+        }
+
+        Ok(count)
+    }
+
+    /// Fetches a single resource by name, served from the cached listing when there is one.
+    pub fn get_resource(
+        &self,
+        namespace: &str,
+        resource: &ApiResource,
+        name: &str,
+    ) -> anyhow::Result<Option<DynamicObject>> {
+        let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+
+        if let Some(objs) = self.cached(&key) {
+            return Ok(objs.into_iter().find(|o| o.name_any() == name));
+        }
+
+        let obj = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+            api.get_opt(name).await
+        });
+        let obj = self.record(
+            format!("getting {} {} in {}", resource.plural, name, namespace),
+            obj,
+        )?;
+
+        Ok(obj)
+    }
+
+    /// Finds a resource in the listing of its kind, fetching it directly when it isn't in there.
+    /// The listing can be older than the lookup that found the resource, e.g. when the resource
+    /// was created after the listing got cached.
+    pub fn find_resource(
+        &self,
+        namespace: &str,
+        resource: &ApiResource,
+        name: &str,
+    ) -> anyhow::Result<Option<DynamicObject>> {
+        let listed = self
+            .list_resources(namespace, resource)?
+            .into_iter()
+            .find(|o| o.name_any() == name);
+
+        match listed {
+            Some(obj) => Ok(Some(obj)),
+            None => self.refresh_resource(namespace, resource, name),
+        }
+    }
+
+    /// Fetches a resource from the api server, bypassing the cache, and updates the cached listing
+    /// it's part of with the result. `None` when the resource no longer exists.
+    pub fn refresh_resource(
+        &self,
+        namespace: &str,
+        resource: &ApiResource,
+        name: &str,
+    ) -> anyhow::Result<Option<DynamicObject>> {
+        let obj = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+            api.get_opt(name).await
+        });
+        let obj = self.record(
+            format!("getting {} {} in {}", resource.plural, name, namespace),
+            obj,
+        )?;
+
+        let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+        if let Some(mut objs) = self.cached(&key) {
+            let position = objs.iter().position(|o| o.name_any() == name);
+            match (position, &obj) {
+                (Some(i), Some(obj)) => objs[i] = obj.clone(),
+                (Some(i), None) => {
+                    objs.remove(i);
+                }
+                (None, Some(obj)) => objs.push(obj.clone()),
+                (None, None) => {}
+            }
+            self.store(key, objs);
+        }
+
+        Ok(obj)
+    }
+
+    /// Fetches a resource as it was at the given resourceVersion. The api server only serves the
+    /// current version of a single resource, older versions are only known when a watch saw them.
+    pub fn get_resource_at_version(
+        &self,
+        namespace: &str,
+        resource: &ApiResource,
+        name: &str,
+        version: &str,
+    ) -> anyhow::Result<Option<DynamicObject>> {
+        let key = format!("{}/{}", namespace, resource.kind.to_lowercase());
+
+        let seen = self
+            .history
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(k, o)| {
+                *k == key
+                    && o.name_any() == name
+                    && o.resource_version().as_deref() == Some(version)
+            })
+            .map(|(_, o)| o.clone());
+
+        if seen.is_some() {
+            return Ok(seen);
+        }
+
+        Ok(self
+            .get_resource(namespace, resource, name)?
+            .filter(|o| o.resource_version().as_deref() == Some(version)))
+    }
+
+    /// Looks up the `additionalPrinterColumns` of the CustomResourceDefinition backing the given
+    /// resource. Resources that aren't backed by a CRD (all built-in kinds) have no columns.
+    pub fn get_printer_columns(
+        &self,
+        resource: &ApiResource,
+    ) -> anyhow::Result<Vec<CustomResourceColumnDefinition>> {
+        Ok(self
+            .get_crd_version(resource)?
+            .and_then(|v| v.additional_printer_columns)
+            .unwrap_or_default())
+    }
+
+    /// Description of a custom resource in the schema of its CustomResourceDefinition. Built-in
+    /// kinds, and CRDs that don't describe their schema, have none.
+    pub fn get_description(&self, resource: &ApiResource) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .get_crd_version(resource)?
+            .and_then(|v| v.schema)
+            .and_then(|s| s.open_api_v3_schema)
+            .and_then(|s| s.description))
+    }
+
+    /// The OpenAPI v3 document of the group version of a resource, holding the schemas of the
+    /// kinds it serves. Documents are large, so they are kept until the caches get cleared.
+    pub fn get_openapi_document(
+        &self,
+        resource: &ApiResource,
+    ) -> anyhow::Result<Arc<serde_json::Value>> {
+        let path = match resource.group.as_str() {
+            "" => format!("/openapi/v3/api/{}", resource.version),
+            _ => format!("/openapi/v3/apis/{}", resource.api_version),
+        };
+
+        if let Some(document) = self.openapi.get(&path) {
+            return Ok(document);
+        }
+
+        let url = path.as_str();
+        let document = self.retry(move || async move {
+            let request = hyper::Request::get(url)
+                .body(Vec::new())
+                .map_err(kube::Error::HttpError)?;
+            self.client().request::<serde_json::Value>(request).await
+        });
+        let document = Arc::new(self.record(format!("getting {}", path), document)?);
+
+        self.openapi.insert(path, document.clone());
+
+        Ok(document)
+    }
+
+    /// The version of the CustomResourceDefinition backing the given resource, `None` for
+    /// resources that aren't backed by a CRD.
+    fn get_crd_version(
+        &self,
+        resource: &ApiResource,
+    ) -> anyhow::Result<Option<CustomResourceDefinitionVersion>> {
+        let key = format!("{}.{}", resource.plural, resource.group);
+
+        if let Some(version) = self.crd_versions.get(&key) {
+            return Ok(version);
+        }
+
+        let name = key.as_str();
+        let crd = self.retry(move || async move {
+            let api: Api<CustomResourceDefinition> = Api::all(self.client());
+            api.get_opt(name).await
+        });
+        let crd = self.record(format!("getting customresourcedefinition {}", key), crd)?;
+
+        let version = crd.and_then(|crd| {
+            crd.spec
+                .versions
+                .into_iter()
+                .find(|v| v.name == resource.version)
+        });
+
+        self.crd_versions.insert(key, version.clone());
+
+        Ok(version)
+    }
+
+    /// Logs of a container of a pod. Reading them again within a few seconds reuses the logs
+    /// fetched before, as long as the container hasn't restarted in between. Following logs
+    /// always goes to the api server.
+    pub fn get_pod_logs(
+        &self,
+        namespace: &str,
+        name: &str,
+        params: &LogParams,
+    ) -> anyhow::Result<Vec<u8>> {
+        if params.follow || self.no_cache {
+            return self.fetch_pod_logs(namespace, name, params);
+        }
+
+        let container = params.container.as_deref();
+        let container_id = match self.get_container_id(namespace, name, container)? {
+            Some(id) => id,
+            None => return self.fetch_pod_logs(namespace, name, params),
+        };
+
+        let key = format!("{}/{}/{}", namespace, name, container.unwrap_or_default());
+        let request = format!("{:?}", params);
+
+        if let Some(cached) = self.logs.get(&key) {
+            if cached.container_id == container_id && cached.params == request {
+                return Ok(cached.logs);
+            }
+        }
+
+        let logs = self.fetch_pod_logs(namespace, name, params)?;
+        self.logs.insert(
+            key,
+            CachedLogs {
+                container_id,
+                params: request,
+                logs: logs.clone(),
+            },
+        );
+
+        Ok(logs)
+    }
+
+    /// Id of the running instance of a container, or of the first container when none is named.
+    /// `None` while the container hasn't been started yet.
+    fn get_container_id(
+        &self,
+        namespace: &str,
+        name: &str,
+        container: Option<&str>,
+    ) -> anyhow::Result<Option<String>> {
+        let pod = self.retry(move || async move {
+            let api: Api<Pod> = Api::namespaced(self.client(), namespace);
+            api.get_opt(name).await
+        });
+        let pod = self.record(format!("getting pod {} in {}", name, namespace), pod)?;
+
+        let id = pod
+            .and_then(|pod| pod.status)
+            .and_then(|status| status.container_statuses)
+            .and_then(|statuses| {
+                statuses
+                    .into_iter()
+                    .find(|s| container.is_none_or(|c| s.name == c))
+            })
+            .and_then(|status| status.container_id);
+
+        Ok(id)
+    }
+
+    fn fetch_pod_logs(
+        &self,
+        namespace: &str,
+        name: &str,
+        params: &LogParams,
+    ) -> anyhow::Result<Vec<u8>> {
+        // Streamed rather than read with `logs`, which fails on anything that isn't valid utf-8.
+        let logs = self.retry(move || async move {
+            let api: Api<Pod> = Api::namespaced(self.client(), namespace);
+            let stream = api.log_stream(name, params).await?;
+            stream
+                .try_fold(Vec::new(), |mut logs, chunk| async move {
+                    logs.extend_from_slice(&chunk);
+                    Ok(logs)
+                })
+                .await
+        });
+        let logs = self.record(
+            format!("getting logs of pod {} in {}", name, namespace),
+            logs,
+        )?;
+
+        Ok(logs)
+    }
+
+    /// The HorizontalPodAutoscalers of a namespace, cached like any other listing.
+    pub fn list_autoscalers(&self, namespace: &str) -> anyhow::Result<Vec<DynamicObject>> {
+        let resource = ApiResource::from_gvk_with_plural(
+            &GroupVersionKind::gvk("autoscaling", "v2", "HorizontalPodAutoscaler"),
+            "horizontalpodautoscalers",
+        );
+
+        self.list_resources(namespace, &resource)
+    }
+
+    /// Every registered api service, including the aggregated ones served outside the apiserver.
+    pub fn list_api_services(&self) -> anyhow::Result<Vec<DynamicObject>> {
+        let resource = ApiResource::from_gvk_with_plural(
+            &GroupVersionKind::gvk("apiregistration.k8s.io", "v1", "APIService"),
+            "apiservices",
+        );
+
+        let resource = &resource;
+        let services = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::all_with(self.client(), resource);
+            api.list(&ListParams::default()).await
+        });
+        let services = self.record("listing api services", services)?;
+
+        Ok(services.items)
+    }
+
+    /// Current cpu and memory usage of every node, served by metrics-server.
+    pub fn node_metrics(&self) -> anyhow::Result<Vec<DynamicObject>> {
+        let resource = ApiResource::from_gvk_with_plural(
+            &GroupVersionKind::gvk("metrics.k8s.io", "v1beta1", "NodeMetrics"),
+            "nodes",
+        );
+
+        let resource = &resource;
+        let metrics = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::all_with(self.client(), resource);
+            api.list(&ListParams::default()).await
+        });
+        let metrics = self.record("listing node metrics", metrics)?;
+
+        Ok(metrics.items)
+    }
+
+    /// Current cpu and memory usage of the containers in a pod, served by metrics-server.
+    pub fn pod_metrics(&self, namespace: &str, name: &str) -> anyhow::Result<DynamicObject> {
+        let resource = ApiResource::from_gvk_with_plural(
+            &GroupVersionKind::gvk("metrics.k8s.io", "v1beta1", "PodMetrics"),
+            "pods",
+        );
+
+        let resource = &resource;
+        let metrics = self.retry(move || async move {
+            let api: Api<DynamicObject> = Api::namespaced_with(self.client(), namespace, resource);
+            api.get(name).await
+        });
+        let metrics = self.record(
+            format!("getting pod metrics {} in {}", name, namespace),
+            metrics,
+        )?;
+
+        Ok(metrics)
+    }
+
+    /// Events about a single object, selected by its uid so events of an earlier object with the
+    /// same name don't show up.
+    pub fn list_events(&self, namespace: &str, uid: &str) -> anyhow::Result<Vec<KubeEvent>> {
+        let selector = format!("involvedObject.uid={}", uid);
+        let selector = selector.as_str();
+        let events = self.retry(move || async move {
+            let api: Api<KubeEvent> = Api::namespaced(self.client(), namespace);
+            list_paged(&api, self.list_params().fields(selector)).await
+        });
+        let events = self.record(
+            format!("listing events of {} in {}", uid, namespace),
+            events,
+        )?;
+
+        Ok(events)
+    }
+
+    fn cached(&self, key: &String) -> Option<Vec<DynamicObject>> {
+        match self.no_cache {
+            true => None,
+            false => self.cache.get(key),
+        }
+    }
+
+    fn store(&self, key: String, objs: Vec<DynamicObject>) {
+        if !self.no_cache {
+            self.cache.insert(key, objs);
+        }
+    }
+}
+
+/// Exponential backoff of a watch failing over and over, capped at `max_backoff`.
+fn watch_backoff(attempt: u32, max_backoff: Duration) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(max_backoff)
+}
+
+/// Builds a client from the kubeconfig or in-cluster configuration, or from the `server`,
+/// `token` and `ca` options when a server is given.
+async fn build_client(options: &KubeFsOptions) -> anyhow::Result<(Client, Connection)> {
+    let config = match &options.server {
+        Some(_) if options.cluster.is_some() || options.user.is_some() => {
+            return Err(anyhow::Error::msg(
+                "Options cluster and user pick from the kubeconfig, they can't be combined with server",
+            ))
+        }
+        Some(_) => {
+            let kubeconfig = server_kubeconfig(options)?;
+            Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default())
+                .await
+                .map_err(anyhow::Error::from)
+        }
+        None if options.cluster.is_some() || options.user.is_some() => {
+            let kubeconfig = Kubeconfig::read().context("Unable to read the kubeconfig")?;
+            let config_options = kubeconfig_overrides(&kubeconfig, options)?;
+            Config::from_custom_kubeconfig(kubeconfig, &config_options)
+                .await
+                .map_err(anyhow::Error::from)
+        }
+        None => Config::infer().await.map_err(anyhow::Error::from),
+    };
+
+    let client = config.and_then(|mut config| {
+        config.connect_timeout = options.connect_timeout.or(config.connect_timeout);
+        let connection = Connection::from(&config);
+        let client = match (options.pool_idle_timeout, options.pool_max_idle) {
+            (None, None) => Client::try_from(config).map_err(anyhow::Error::from),
+            _ => pooled_client(config, options),
+        };
+        client.map(|client| (client, connection))
+    });
+
+    client.with_context(|| match &options.server {
+        Some(server) => format!("Unable to configure a client for {}", server),
+        None => String::from("Unable to load a kubeconfig or in-cluster configuration"),
+    })
+}
+
+/// Builds the same stack as `Client::try_from` around an http client with the connection pool
+/// configured through `pool_idle_timeout` and `pool_max_idle`.
+fn pooled_client(config: Config, options: &KubeFsOptions) -> anyhow::Result<Client> {
+    let mut connector = HttpConnector::new();
+    connector.enforce_http(false);
+    let connector = config.openssl_https_connector_with_connector(connector)?;
+
+    let mut connector = TimeoutConnector::new(connector);
+    connector.set_connect_timeout(config.connect_timeout);
+    connector.set_read_timeout(config.read_timeout);
+    connector.set_write_timeout(config.write_timeout);
+
+    let mut builder = hyper::Client::builder();
+    if let Some(timeout) = options.pool_idle_timeout {
+        builder.pool_idle_timeout(timeout);
+    }
+    if let Some(max_idle) = options.pool_max_idle {
+        builder.pool_max_idle_per_host(max_idle);
+    }
+
+    let service = ServiceBuilder::new()
+        .layer(config.base_uri_layer())
+        .option_layer(config.auth_layer()?)
+        .layer(config.extra_headers_layer()?)
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|req: &hyper::Request<hyper::Body>| {
+                debug_span!("HTTP", http.method = %req.method(), http.url = %req.uri())
+            }),
+        )
+        .service(builder.build::<_, hyper::Body>(connector));
+
+    Ok(Client::new(service, config.default_namespace))
+}
+
+/// Whether a process has SIGKILL pending, which is how the kernel marks a process that got
+/// killed while it's stuck waiting on a reply. False when its status can't be read.
+fn is_killed(pid: u32) -> bool {
+    let status = match std::fs::read_to_string(format!("/proc/{}/status", pid)) {
+        Ok(status) => status,
+        Err(_) => return false,
+    };
+
+    status
+        .lines()
+        .filter_map(|l| l.strip_prefix("SigPnd:").or(l.strip_prefix("ShdPnd:")))
+        .filter_map(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+        .any(|mask| mask & SIGKILL_MASK != 0)
+}
+
+/// Stops the watches behind the informers, so the next listing starts a new one with the client
+/// it's listed with.
+fn stop_informers(informers: &Option<Informers>) {
+    if let Some(informers) = informers {
+        for (_, informer) in informers.lock().unwrap().drain() {
+            informer.task.abort();
+        }
+    }
+}
+
+/// Watches the namespaces `client` can see, evicting the cached namespace listing on every
+/// change.
+fn spawn_namespace_watch(
+    handle: &Handle,
+    client: Client,
+    cache: Cache<String, Vec<DynamicObject>>,
+    params: ListParams,
+) -> JoinHandle<()> {
+    let api: Api<Namespace> = Api::all(client);
+
+    handle.spawn(async move {
+        let mut events = watcher(api, params).boxed();
+
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(_) => cache.invalidate(&String::from("namespaces")),
+                Err(err) => {
+                    warn!("Namespace watch failed: {}", err);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    })
+}
+
+/// Moves a running namespace watch over to a new client, the namespaces of the old one no
+/// longer make up the root of the tree. Does nothing when namespaces aren't watched.
+fn restart_namespace_watch(
+    watch: &Mutex<Option<JoinHandle<()>>>,
+    handle: &Handle,
+    client: Client,
+    cache: Cache<String, Vec<DynamicObject>>,
+    params: ListParams,
+) {
+    let mut watch = watch.lock().unwrap();
+    if let Some(task) = watch.take() {
+        task.abort();
+        *watch = Some(spawn_namespace_watch(handle, client, cache, params));
+    }
+}
+
+/// Current context of the kubeconfig. Mounting a server directly doesn't involve a kubeconfig,
+/// so there is no context either.
+fn read_context(options: &KubeFsOptions) -> Option<String> {
+    match options.server {
+        Some(_) => None,
+        None => Kubeconfig::read().ok().and_then(|k| k.current_context),
+    }
+}
+
+/// Location of the kubeconfig: the first file in `KUBECONFIG`, or `~/.kube/config`.
+fn kubeconfig_path() -> Option<PathBuf> {
+    match env::var_os("KUBECONFIG") {
+        Some(paths) => env::split_paths(&paths).next(),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".kube").join("config")),
+    }
+}
+
+/// The cluster and user of `-o cluster=` and `-o user=` to use instead of the ones of the current
+/// context, after checking the kubeconfig has them.
+fn kubeconfig_overrides(
+    kubeconfig: &Kubeconfig,
+    options: &KubeFsOptions,
+) -> anyhow::Result<KubeConfigOptions> {
+    if let Some(cluster) = &options.cluster {
+        if !kubeconfig.clusters.iter().any(|c| &c.name == cluster) {
+            return Err(anyhow::Error::msg(format!(
+                "Cluster {} not found in the kubeconfig",
+                cluster
+            )));
+        }
+    }
+
+    if let Some(user) = &options.user {
+        if !kubeconfig.auth_infos.iter().any(|u| &u.name == user) {
+            return Err(anyhow::Error::msg(format!(
+                "User {} not found in the kubeconfig",
+                user
+            )));
+        }
+    }
+
+    Ok(KubeConfigOptions {
+        context: None,
+        cluster: options.cluster.clone(),
+        user: options.user.clone(),
+    })
+}
+
+/// Kubeconfig with a single context pointing at the server given through `-o server=`, so a
+/// cluster can be mounted without a kubeconfig file.
+fn server_kubeconfig(options: &KubeFsOptions) -> anyhow::Result<Kubeconfig> {
+    let kubeconfig = serde_json::from_value(serde_json::json!({
+        "clusters": [{
+            "name": "kubefs",
+            "cluster": {
+                "server": options.server,
+                "certificate-authority": options.ca,
+                "insecure-skip-tls-verify": options.insecure_skip_tls_verify,
+            },
+        }],
+        "users": [{
+            "name": "kubefs",
+            "user": { "token": options.token },
+        }],
+        "contexts": [{
+            "name": "kubefs",
+            "context": { "cluster": "kubefs", "user": "kubefs" },
+        }],
+        "current-context": "kubefs",
+    }))?;
+
+    Ok(kubeconfig)
+}
+
+/// Lists in pages of `LIST_PAGE_SIZE`, which bounds the work the api server does per request for
+/// kinds with lots of resources. The timeout in `params` only goes to the server for watches,
+/// kube doesn't send it along with a plain list, so here it bounds every page instead. A page
+/// that takes too long is asked for again in halves, down to `MIN_LIST_PAGE_SIZE`.
+async fn list_paged<K>(api: &Api<K>, params: ListParams) -> Result<Vec<K>, kube::Error>
+where
+    K: Clone + DeserializeOwned + Debug,
+{
+    Ok(list_paged_versioned(api, params).await?.0)
+}
+
+/// Lists like `list_paged`, along with the resourceVersion of the listing to watch from.
+async fn list_paged_versioned<K>(
+    api: &Api<K>,
+    params: ListParams,
+) -> Result<(Vec<K>, Option<String>), kube::Error>
+where
+    K: Clone + DeserializeOwned + Debug,
+{
+    let timeout = params.timeout.map(|t| Duration::from_secs(t.into()));
+    let mut params = params.limit(LIST_PAGE_SIZE);
+    let mut items = Vec::new();
+
+    loop {
+        let page = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, api.list(&params)).await {
+                Ok(page) => page?,
+                Err(_) if params.limit > Some(MIN_LIST_PAGE_SIZE) => {
+                    let limit = params.limit.unwrap_or(LIST_PAGE_SIZE);
+                    warn!(
+                        "Listing a page of {} timed out, trying smaller pages",
+                        limit
+                    );
+                    params.limit = Some((limit / 2).max(MIN_LIST_PAGE_SIZE));
+                    continue;
+                }
+                Err(elapsed) => return Err(kube::Error::Service(Box::new(elapsed))),
+            },
+            None => api.list(&params).await?,
+        };
+        items.extend(page.items);
+
+        match page.metadata.continue_ {
+            Some(token) if !token.is_empty() => params = params.continue_token(&token),
+            _ => return Ok((items, page.metadata.resource_version)),
+        }
+    }
+}
+
+/// A write the api server turned down, by validation or an admission webhook like OPA or
+/// Kyverno, comes with a Status saying why. Its reason and message become the error, one the
+/// filesystem maps onto a fitting errno instead of EIO.
+fn rejected_write(err: kube::Error) -> anyhow::Error {
+    let response = match &err {
+        kube::Error::Api(response) => response,
+        _ => return err.into(),
+    };
+
+    let kind = match response.code {
+        401 | 403 => io::ErrorKind::PermissionDenied,
+        404 => io::ErrorKind::NotFound,
+        400 | 409 | 422 => io::ErrorKind::InvalidInput,
+        _ => return err.into(),
+    };
+
+    io::Error::new(kind, format!("{}: {}", response.reason, response.message)).into()
+}
+
+/// A random duration up to `max`. Spreading retries doesn't need a proper random number
+/// generator: every `RandomState` gets fresh random keys, seeded from the OS once per thread, so
+/// the hash of nothing at all is a random number already.
+fn jitter(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    max.mul_f64(random)
+}
+
+fn is_transient(err: &kube::Error) -> bool {
+    match err {
+        kube::Error::Api(response) => response.code == 429 || response.code >= 500,
+        kube::Error::Service(err) => !err.is::<RequestAbandoned>(),
+        kube::Error::HyperError(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether an error was caused by an io timeout somewhere down the chain, which is how the
+/// connect timeout of the underlying http client surfaces.
+fn is_timeout(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            if io.kind() == std::io::ErrorKind::TimedOut {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+
+    false
+}
+
+/// A mock api server to test against, for the tests of the client and of the virtual filesystem
+/// built on top of it.
+#[cfg(test)]
+pub mod mock {
+    use http::{Request, Response, StatusCode};
+    use hyper::Body;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    pub type Reply = Result<(StatusCode, Value), std::io::Error>;
+
+    /// A `KubeClient` talking to a mock api server, `handler` answers every request by its path.
+    pub fn mock_client(handler: impl Fn(&str) -> Reply + Send + Sync + 'static) -> KubeClient {
+        let options = KubeFsOptions {
+            max_backoff: Duration::from_millis(1),
+            ..KubeFsOptions::default()
+        };
+        mock_client_with(&options, handler)
+    }
+
+    pub fn mock_client_with(
+        options: &KubeFsOptions,
+        handler: impl Fn(&str) -> Reply + Send + Sync + 'static,
+    ) -> KubeClient {
+        let runtime = Runtime::new().unwrap();
+        let handler = Arc::new(handler);
+        let service = tower::service_fn(move |request: Request<Body>| {
+            let reply = handler(request.uri().path());
+            async move {
+                let (status, body) = reply?;
+                Ok::<_, std::io::Error>(
+                    Response::builder()
+                        .status(status)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+            }
+        });
+
+        let client = {
+            let _guard = runtime.enter();
+            Client::new(service, "default")
+        };
+
+        KubeClient::with_client(client, runtime, options).unwrap()
+    }
+
+    pub fn status(code: StatusCode) -> Reply {
+        Ok((
+            code,
+            json!({
+                "kind": "Status",
+                "apiVersion": "v1",
+                "metadata": {},
+                "status": "Failure",
+                "message": code.canonical_reason(),
+                "reason": code.canonical_reason(),
+                "code": code.as_u16(),
+            }),
+        ))
+    }
+
+    /// A 404 with the `NotFound` reason the api server gives for a missing resource, which is
+    /// what tells kube a get found nothing rather than failed.
+    pub fn not_found() -> Reply {
+        let (code, mut body) = status(StatusCode::NOT_FOUND)?;
+        body["reason"] = json!("NotFound");
+        Ok((code, body))
+    }
+
+    pub fn list(kind: &str, names: &[&str]) -> Reply {
+        let items: Vec<Value> = names
+            .iter()
+            .map(|name| json!({ "metadata": { "name": name, "namespace": "default" } }))
+            .collect();
+
+        Ok((
+            StatusCode::OK,
+            json!({
+                "kind": format!("{}List", kind),
+                "apiVersion": "v1",
+                "metadata": { "resourceVersion": "1" },
+                "items": items,
+            }),
+        ))
+    }
+
+    pub fn resource(name: &str, kind: &str, namespaced: bool) -> Value {
+        json!({
+            "name": name,
+            "singularName": kind.to_lowercase(),
+            "namespaced": namespaced,
+            "kind": kind,
+            "verbs": ["get", "list", "watch"],
+        })
+    }
+
+    pub fn group(name: &str, version: &str) -> Value {
+        let version = json!({
+            "groupVersion": format!("{}/{}", name, version),
+            "version": version,
+        });
+        json!({ "name": name, "versions": [version], "preferredVersion": version })
+    }
+
+    /// A cluster with the core and apps groups, next to an aggregated metrics api that is down.
+    pub fn discovery(path: &str) -> Reply {
+        match path {
+            "/api" => Ok((
+                StatusCode::OK,
+                json!({
+                    "kind": "APIVersions",
+                    "versions": ["v1"],
+                    "serverAddressByClientCIDRs": [],
+                }),
+            )),
+            "/apis" => Ok((
+                StatusCode::OK,
+                json!({
+                    "kind": "APIGroupList",
+                    "apiVersion": "v1",
+                    "groups": [group("apps", "v1"), group("metrics.k8s.io", "v1beta1")],
+                }),
+            )),
+            "/api/v1" => {
+                let mut pods = resource("pods", "Pod", true);
+                pods["shortNames"] = json!(["po"]);
+                Ok((
+                    StatusCode::OK,
+                    json!({
+                        "kind": "APIResourceList",
+                        "groupVersion": "v1",
+                        "resources": [resource("namespaces", "Namespace", false), pods],
+                    }),
+                ))
+            }
+            "/apis/apps/v1" => Ok((
+                StatusCode::OK,
+                json!({
+                    "kind": "APIResourceList",
+                    "groupVersion": "apps/v1",
+                    "resources": [resource("deployments", "Deployment", true)],
+                }),
+            )),
+            _ => status(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use http::StatusCode;
+    use serde_json::json;
+
+    use super::mock::*;
+    use super::*;
+
+    fn pods() -> ApiResource {
+        ApiResource::from_gvk(&GroupVersionKind::gvk("", "v1", "Pod"))
+    }
+
+    #[test]
+    fn jitters_up_to_the_max() {
+        let max = Duration::from_millis(200);
+        let delays: HashSet<Duration> = (0..100).map(|_| jitter(max)).collect();
+
+        assert!(delays.iter().all(|d| *d <= max));
+        assert!(delays.len() > 1);
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[test]
+    fn discovers_api_resources_and_skips_broken_groups() {
+        let client = mock_client(discovery);
+
+        let mut kinds: Vec<String> = client
+            .discover_api_resources(None, None)
+            .unwrap()
+            .into_iter()
+            .map(|(r, _)| r.kind)
+            .collect();
+        kinds.sort();
+
+        assert_eq!(kinds, vec!["Deployment", "Namespace", "Pod"]);
+
+        let errors = client.recent_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .message
+            .starts_with("discovering metrics.k8s.io/v1beta1"));
+    }
+
+    #[test]
+    fn discovers_only_the_filtered_groups() {
+        let client = mock_client(discovery);
+
+        let resources = client
+            .discover_api_resources(Some(&vec![""]), None)
+            .unwrap();
+
+        assert_eq!(resources.len(), 2);
+        assert!(resources.iter().all(|(r, _)| r.group.is_empty()));
+        assert!(client.recent_errors().is_empty());
+    }
+
+    #[test]
+    fn fails_discovery_when_groups_cannot_be_listed() {
+        let client = mock_client(|_| status(StatusCode::FORBIDDEN));
+
+        assert!(client.discover_api_resources(None, None).is_err());
+        assert_eq!(client.recent_errors().len(), 1);
+    }
+
+    #[test]
+    fn lists_and_caches_namespaces() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |path| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match path {
+                "/api/v1/namespaces" => list("Namespace", &["default", "kube-system"]),
+                _ => status(StatusCode::NOT_FOUND),
+            }
+        });
+
+        let namespaces = client.list_namespaces().unwrap();
+        let names: Vec<String> = namespaces.iter().map(|n| n.name_any()).collect();
+        assert_eq!(names, vec!["default", "kube-system"]);
+        assert_eq!(namespaces[0].types.as_ref().unwrap().kind, "Namespace");
+
+        client.list_namespaces().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn lists_again_without_a_cache() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let options = KubeFsOptions {
+            no_cache: true,
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            list("Pod", &["web"])
+        });
+
+        client.list_resources("default", &pods()).unwrap();
+        client.list_resources("default", &pods()).unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn caches_resource_counts() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            list("Pod", &["web"])
+        });
+
+        assert_eq!(client.count_resources("default", &pods()).unwrap(), 1);
+        assert_eq!(client.count_resources("default", &pods()).unwrap(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn refetches_listings_after_the_cache_ttl() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let options = KubeFsOptions {
+            cache_ttl: Some(Duration::from_millis(50)),
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            list("Namespace", &["default"])
+        });
+
+        client.list_namespaces().unwrap();
+        client.list_namespaces().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        std::thread::sleep(Duration::from_millis(100));
+        client.list_namespaces().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn prefetches_browsed_listings_before_they_expire() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let options = KubeFsOptions {
+            cache_ttl: Some(Duration::from_millis(100)),
+            prefetch: Some(1),
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            list("Pod", &["web"])
+        });
+
+        client.list_resources("default", &pods()).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(requests.load(Ordering::SeqCst) > 1);
+    }
+
+    #[test]
+    fn lists_resources() {
+        let client = mock_client(|path| match path {
+            "/api/v1/namespaces/default/pods" => list("Pod", &["web", "db"]),
+            _ => status(StatusCode::NOT_FOUND),
+        });
+
+        let names: Vec<String> = client
+            .list_resources("default", &pods())
+            .unwrap()
+            .iter()
+            .map(|p| p.name_any())
+            .collect();
+
+        assert_eq!(names, vec!["web", "db"]);
+    }
+
+    #[test]
+    fn refreshes_a_resource_in_the_cached_listing() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |path| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match path {
+                "/api/v1/namespaces/default/pods" => list("Pod", &["web", "db"]),
+                "/api/v1/namespaces/default/pods/web" => Ok((
+                    StatusCode::OK,
+                    json!({
+                        "metadata": { "name": "web", "namespace": "default", "labels": { "v": "2" } },
+                    }),
+                )),
+                _ => not_found(),
+            }
+        });
+
+        client.list_resources("default", &pods()).unwrap();
+
+        let web = client.refresh_resource("default", &pods(), "web").unwrap();
+        assert_eq!(web.unwrap().labels()["v"], "2");
+        assert!(client
+            .refresh_resource("default", &pods(), "db")
+            .unwrap()
+            .is_none());
+
+        let listed = client.list_resources("default", &pods()).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].labels()["v"], "2");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn finds_resources_missing_from_the_cached_listing() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |path| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match path {
+                "/api/v1/namespaces/default/pods" => list("Pod", &["web"]),
+                "/api/v1/namespaces/default/pods/db" => Ok((
+                    StatusCode::OK,
+                    json!({ "metadata": { "name": "db", "namespace": "default" } }),
+                )),
+                _ => not_found(),
+            }
+        });
+
+        client.list_resources("default", &pods()).unwrap();
+
+        let db = client.find_resource("default", &pods(), "db").unwrap();
+        assert_eq!(db.unwrap().name_any(), "db");
+        assert!(client
+            .find_resource("default", &pods(), "cache")
+            .unwrap()
+            .is_none());
+
+        assert_eq!(client.list_resources("default", &pods()).unwrap().len(), 2);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn retries_conflicting_applies_on_the_current_version() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |path| match path {
+            "/api/v1/namespaces/default/pods/web" => {
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    // The stale apply, then the get for the current version.
+                    0 => status(StatusCode::CONFLICT),
+                    1 => Ok((
+                        StatusCode::OK,
+                        json!({
+                            "metadata": {
+                                "name": "web",
+                                "namespace": "default",
+                                "resourceVersion": "2",
+                            },
+                        }),
+                    )),
+                    _ => Ok((
+                        StatusCode::OK,
+                        json!({ "metadata": { "name": "web", "namespace": "default" } }),
+                    )),
+                }
+            }
+            _ => status(StatusCode::NOT_FOUND),
+        });
+
+        let mut obj = DynamicObject::new("web", &pods()).within("default");
+        obj.metadata.resource_version = Some("1".into());
+        client
+            .apply_resource(Some("default"), &pods(), "web", &obj)
+            .unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn applies_resources_and_updates_the_cached_listing() {
+        let client = mock_client(|path| match path {
+            "/api/v1/namespaces/default/pods" => list("Pod", &["web"]),
+            "/api/v1/namespaces/default/pods/web" => Ok((
+                StatusCode::OK,
+                json!({
+                    "metadata": { "name": "web", "namespace": "default", "labels": { "v": "2" } },
+                }),
+            )),
+            _ => status(StatusCode::NOT_FOUND),
+        });
+
+        client.list_resources("default", &pods()).unwrap();
+
+        let obj = DynamicObject::new("web", &pods()).within("default");
+        client
+            .apply_resource(Some("default"), &pods(), "web", &obj)
+            .unwrap();
+
+        let listed = client.list_resources("default", &pods()).unwrap();
+        assert_eq!(listed[0].labels()["v"], "2");
+
+        assert!(client
+            .apply_resource(Some("default"), &pods(), "db", &obj)
+            .is_err());
+    }
+
+    #[test]
+    fn does_not_retry_forbidden_requests() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            status(StatusCode::FORBIDDEN)
+        });
+
+        let err = client.list_resources("default", &pods()).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<kube::Error>(),
+            Some(kube::Error::Api(response)) if response.code == 403
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(client.recent_errors()[0]
+            .message
+            .starts_with("listing pods in default"));
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => status(StatusCode::INTERNAL_SERVER_ERROR),
+            _ => list("Pod", &["web"]),
+        });
+
+        let pods = client.list_resources("default", &pods()).unwrap();
+
+        assert_eq!(pods.len(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert!(client.recent_errors().is_empty());
+    }
+
+    #[test]
+    fn gives_up_after_repeated_server_errors() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let client = mock_client(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            status(StatusCode::INTERNAL_SERVER_ERROR)
+        });
+
+        assert!(client.list_namespaces().is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), MAX_RETRIES as usize + 1);
+    }
+
+    #[test]
+    fn reuses_logs_until_the_container_restarts() {
+        let restarts = Arc::new(AtomicUsize::new(0));
+        let log_requests = Arc::new(AtomicUsize::new(0));
+        let (restarted, fetched) = (restarts.clone(), log_requests.clone());
+        let client = mock_client(move |path| match path {
+            "/api/v1/namespaces/default/pods/web" => Ok((
+                StatusCode::OK,
+                json!({
+                    "kind": "Pod",
+                    "apiVersion": "v1",
+                    "metadata": { "name": "web", "namespace": "default" },
+                    "status": {
+                        "containerStatuses": [{
+                            "name": "app",
+                            "containerID": format!("containerd://{}", restarted.load(Ordering::SeqCst)),
+                            "image": "nginx",
+                            "imageID": "",
+                            "ready": true,
+                            "restartCount": 0,
+                        }],
+                    },
+                }),
+            )),
+            "/api/v1/namespaces/default/pods/web/log" => Ok((
+                StatusCode::OK,
+                json!(fetched.fetch_add(1, Ordering::SeqCst)),
+            )),
+            _ => status(StatusCode::NOT_FOUND),
+        });
+        let params = LogParams {
+            container: Some("app".into()),
+            ..LogParams::default()
+        };
+
+        let first = client.get_pod_logs("default", "web", &params).unwrap();
+        let second = client.get_pod_logs("default", "web", &params).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(log_requests.load(Ordering::SeqCst), 1);
+
+        restarts.fetch_add(1, Ordering::SeqCst);
+        let restarted = client.get_pod_logs("default", "web", &params).unwrap();
+        assert_ne!(first, restarted);
+        assert_eq!(log_requests.load(Ordering::SeqCst), 2);
+
+        let follow = LogParams {
+            follow: true,
+            ..params
+        };
+        client.get_pod_logs("default", "web", &follow).unwrap();
+        assert_eq!(log_requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn rejects_cluster_and_user_overrides_missing_from_the_kubeconfig() {
+        let kubeconfig: Kubeconfig = serde_json::from_value(json!({
+            "clusters": [{ "name": "prod", "cluster": { "server": "https://prod:6443" } }],
+            "users": [{ "name": "admin", "user": { "token": "secret" } }],
+        }))
+        .unwrap();
+        let options = |cluster: &str, user: &str| KubeFsOptions {
+            cluster: Some(cluster.into()),
+            user: Some(user.into()),
+            ..KubeFsOptions::default()
+        };
+
+        let overrides = kubeconfig_overrides(&kubeconfig, &options("prod", "admin")).unwrap();
+        assert_eq!(overrides.cluster.as_deref(), Some("prod"));
+        assert_eq!(overrides.user.as_deref(), Some("admin"));
+
+        let err = kubeconfig_overrides(&kubeconfig, &options("prod", "viewer")).err();
+        assert_eq!(
+            err.map(|e| e.to_string()).as_deref(),
+            Some("User viewer not found in the kubeconfig")
+        );
+    }
+
+    #[test]
+    fn surfaces_why_a_webhook_rejected_a_write() {
+        let client = mock_client(|_| {
+            Ok((
+                StatusCode::BAD_REQUEST,
+                json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "metadata": {},
+                    "status": "Failure",
+                    "message": "admission webhook \"validate.kyverno.svc\" denied the request: image tag latest is not allowed",
+                    "reason": "BadRequest",
+                    "code": 400,
+                }),
+            ))
+        });
+
+        let err = client
+            .patch_resource(
+                "default",
+                &pods(),
+                "web",
+                &Patch::Merge(json!({ "spec": { "image": "nginx:latest" } })),
+            )
+            .unwrap_err();
+
+        let err = err.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "BadRequest: admission webhook \"validate.kyverno.svc\" denied the request: image tag latest is not allowed"
+        );
+    }
+
+    #[test]
+    fn reports_connect_timeouts() {
+        let options = KubeFsOptions {
+            connect_timeout: Some(Duration::from_secs(3)),
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, |_| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "connection timed out",
+            ))
+        });
+
+        let err = client.cluster_info().unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Could not connect to the api server within 3s"
+        );
+    }
+
+    #[test]
+    fn lists_in_smaller_pages_after_a_timeout() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let seen = queries.clone();
+        let service = tower::service_fn(move |request: http::Request<hyper::Body>| {
+            let query = request.uri().query().unwrap_or_default().to_string();
+            seen.lock().unwrap().push(query.clone());
+            async move {
+                if query.contains("limit=500") {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                let (status, body) = list("Pod", &["a", "b"])?;
+                Ok::<_, std::io::Error>(
+                    http::Response::builder()
+                        .status(status)
+                        .header("content-type", "application/json")
+                        .body(hyper::Body::from(body.to_string()))
+                        .unwrap(),
+                )
+            }
+        });
+
+        let runtime = Runtime::new().unwrap();
+        let (pods, _) = runtime
+            .block_on(async {
+                let client = Client::new(service, "default");
+                let api: Api<Pod> = Api::namespaced(client, "default");
+                list_paged_versioned(&api, ListParams::default().timeout(1)).await
+            })
+            .unwrap();
+
+        assert_eq!(pods.len(), 2);
+        let queries = queries.lock().unwrap();
+        assert_eq!(queries.len(), 2);
+        assert!(queries[0].contains("limit=500"));
+        assert!(queries[1].contains("limit=250"));
+    }
 }
@@ -1,16 +1,28 @@
-use crate::client::KubeClient;
+use crate::client::{KubeClient, RequestAbandoned};
+use crate::options::KubeFsOptions;
 use crate::vfs::KubeVirtualFs;
+use anyhow::Context;
 use daemonize::{Daemonize, Outcome};
 use fuser::{
-    Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
-    ReplyOpen, Request,
+    consts::{FOPEN_DIRECT_IO, FUSE_DO_READDIRPLUS},
+    Filesystem, KernelConfig, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
+    ReplyDirectoryPlus, ReplyEmpty, ReplyEntry, ReplyLseek, ReplyOpen, ReplyStatfs, ReplyWrite,
+    ReplyXattr, Request, TimeOrNow, FUSE_ROOT_ID,
+};
+use libc::{
+    c_int, EACCES, EINTR, EINVAL, EIO, ENODATA, ENOENT, ENOTEMPTY, ENXIO, ERANGE, SEEK_DATA,
+    SEEK_END, SEEK_HOLE, SEEK_SET, SIGHUP,
 };
-use libc::ENOENT;
 use std::ffi::OsStr;
 use std::fs::{self, File, Metadata};
+use std::io;
+use std::path::Path;
 // use std::os::linux::fs::MetadataExt;
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 use std::time::{Duration, SystemTime};
+use tracing::{debug_span, info, warn};
 
 pub struct KubeFuse {
     kube_vfs: KubeVirtualFs,
@@ -19,64 +31,279 @@ pub struct KubeFuse {
 }
 
 impl KubeFuse {
-    pub fn new(mount_point: &str) -> Self {
-        let kube_client = KubeClient::new();
-        let kube_vfs = KubeVirtualFs::new(kube_client);
-        let meta = fs::metadata(mount_point).unwrap();
-        KubeFuse {
+    pub fn new(mount_point: &str, kube_options: &KubeFsOptions) -> anyhow::Result<Self> {
+        let kube_client = KubeClient::new(kube_options)?;
+        KubeFuse::preflight(&kube_client)?;
+
+        let kube_vfs = KubeVirtualFs::new(kube_client, kube_options)?;
+        let meta = fs::metadata(mount_point)?;
+
+        // `kill -HUP` reconnects and flushes the caches, the way daemons reload.
+        unsafe {
+            libc::signal(
+                SIGHUP,
+                request_reload as extern "C" fn(c_int) as libc::sighandler_t,
+            );
+        }
+
+        Ok(KubeFuse {
             kube_vfs,
             mount_metadata: meta,
             startup: SystemTime::now(),
-        }
+        })
     }
 
-    pub fn mount(mountpoint: &str, options: &Vec<MountOption>) -> anyhow::Result<()> {
-        let fuse = KubeFuse::new(mountpoint);
-        fuser::mount2(fuse, mountpoint, &options)?;
+    /// Checks that the api server is reachable and that the credentials are good enough to run
+    /// discovery, so a broken setup fails the mount instead of erroring on every operation.
+    fn preflight(kube_client: &KubeClient) -> anyhow::Result<()> {
+        let info = kube_client.cluster_info().context(
+            "Unable to reach the Kubernetes api server, make sure the cluster is up and the credentials in your kubeconfig are valid",
+        )?;
+        info!("Connected to Kubernetes {}", info.git_version);
+
+        kube_client
+            .discover_api_resources(Some(&vec![""]), None)
+            .context(
+                "Unable to discover api resources, make sure your credentials allow listing them",
+            )?;
+
         Ok(())
     }
 
-    pub fn mount_as_daemon(mountpoint: &str, options: &Vec<MountOption>) -> anyhow::Result<()> {
+    pub fn mount(
+        mountpoint: &str,
+        options: &Vec<MountOption>,
+        kube_options: &KubeFsOptions,
+    ) -> anyhow::Result<()> {
+        let fuse = KubeFuse::new(mountpoint, kube_options)?;
+        let session = fuser::spawn_mount2(fuse, mountpoint, options)?;
+        let mountpoint = fs::canonicalize(&session.mountpoint)?;
+
+        // The session ends by itself once unmounted. When the mount point is removed or the file
+        // system it lives on goes away, the mount goes stale instead and the session would sit
+        // there waiting for requests that never come, so keep an eye on the mount table.
+        loop {
+            if session.guard.is_finished() {
+                return match session.guard.join() {
+                    Ok(result) => Ok(result?),
+                    Err(_) => Err(anyhow::Error::msg("Filesystem session panicked")),
+                };
+            }
+
+            if !is_mounted(&mountpoint) {
+                warn!(
+                    "Mount point {} is gone, shutting down",
+                    mountpoint.display()
+                );
+                // Dropping the session unmounts whatever is left of the mount.
+                return Ok(());
+            }
+
+            thread::sleep(MOUNT_CHECK_INTERVAL);
+        }
+    }
+
+    pub fn mount_as_daemon(
+        mountpoint: &str,
+        options: &Vec<MountOption>,
+        kube_options: &KubeFsOptions,
+    ) -> anyhow::Result<()> {
         let stdout = File::create("/tmp/daemon.out").unwrap();
         let stderr = File::create("/tmp/daemon.err").unwrap();
 
-        let daemon = Daemonize::new().stdout(stdout).stderr(stderr);
+        // Run the preflight before forking so a broken setup is reported on the terminal rather
+        // than only in the daemon logs. The client is dropped again before we fork.
+        KubeFuse::preflight(&KubeClient::new(kube_options)?)?;
+
+        // The daemon changes its working directory, so a relative mount point has to be
+        // resolved up front.
+        let mountpoint = fs::canonicalize(mountpoint)
+            .with_context(|| format!("Mount point {} not found", mountpoint))?;
+        let mountpoint = mountpoint.to_string_lossy();
+
+        let daemon = Daemonize::new()
+            .working_directory(&kube_options.workdir)
+            .umask(kube_options.umask)
+            .stdout(stdout)
+            .stderr(stderr);
 
         match daemon.execute() {
             Outcome::Parent(Ok(p)) => exit(p.first_child_exit_code),
             Outcome::Parent(Err(err)) => Err(err.into()),
             Outcome::Child(Ok(_)) => {
-                KubeFuse::mount(&mountpoint, &options)?;
+                KubeFuse::mount(&mountpoint, options, kube_options)?;
                 Ok(())
             }
             Outcome::Child(Err(err)) => Err(err.into()),
         }
     }
+
+    /// Maps an inode from the kernel onto the tree. The kernel always refers to the root as
+    /// inode 1, which only is the id of the root in the tree when `inode_base` isn't set.
+    fn inode(&self, ino: u64) -> u64 {
+        match ino {
+            FUSE_ROOT_ID => self.kube_vfs.root_inode(),
+            ino => ino,
+        }
+    }
+
+    /// Makes the requests that follow cancellable by the process behind `req`, so a slow read
+    /// doesn't hold up the mount after its caller got killed. Writes pass `None` and always run to
+    /// completion, cancelling one halfway leaves it unclear whether it went through.
+    fn cancellable_by(&self, req: Option<&Request>) {
+        self.kube_vfs.set_caller(req.map(|req| req.pid()));
+    }
+
+    /// Reloads when a SIGHUP came in since the last operation. Runs on the thread dispatching
+    /// the filesystem operations, so no operation sees a half reloaded state.
+    fn reload_if_requested(&mut self) {
+        if RELOAD_REQUESTED.swap(false, Ordering::SeqCst) {
+            info!("Received SIGHUP, reloading");
+            if let Err(err) = self.kube_vfs.reload() {
+                warn!("Unable to reload: {:#}", err);
+            }
+        }
+    }
 }
 
 const TTL: Duration = Duration::from_secs(1); // 1 second
 
+/// How often the mount table is checked for the mount point while mounted.
+const MOUNT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Whether the mount point still shows up in the mount table. Assumes it does when the mount
+/// table can't be read, it's better to keep running than to exit over a missing /proc.
+fn is_mounted(mountpoint: &Path) -> bool {
+    match fs::read_to_string("/proc/self/mounts") {
+        Ok(mounts) => is_listed_in_mounts(&mounts, mountpoint),
+        Err(_) => true,
+    }
+}
+
+/// Looks for the mount point in the second column of a mount table in the fstab(5) format,
+/// where spaces and the like are escaped as octal.
+fn is_listed_in_mounts(mounts: &str, mountpoint: &Path) -> bool {
+    let mountpoint = mountpoint
+        .to_string_lossy()
+        .replace('\\', "\\134")
+        .replace(' ', "\\040")
+        .replace('\t', "\\011")
+        .replace('\n', "\\012");
+
+    mounts
+        .lines()
+        .filter_map(|line| line.split(' ').nth(1))
+        .any(|path| path == mountpoint)
+}
+
+/// Set by the SIGHUP handler. Reloading takes locks and does requests, none of which is allowed
+/// in a signal handler, so the next filesystem operation picks it up instead.
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_reload(_signal: c_int) {
+    RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Maps the io errors the virtual filesystem uses for bad input and permissions onto an errno,
+/// anything else is reported as an io error. Requests cancelled because their caller got killed
+/// are interrupted.
+fn error_code(err: &anyhow::Error) -> c_int {
+    if err.chain().any(|e| e.is::<RequestAbandoned>()) {
+        return EINTR;
+    }
+
+    match err.downcast_ref::<io::Error>().map(|e| e.kind()) {
+        Some(io::ErrorKind::InvalidInput) => EINVAL,
+        Some(io::ErrorKind::PermissionDenied) => EACCES,
+        Some(io::ErrorKind::NotFound) => ENOENT,
+        Some(io::ErrorKind::DirectoryNotEmpty) => ENOTEMPTY,
+        _ => EIO,
+    }
+}
+
+/// Answers an xattr request, a `size` of 0 asks how large a buffer the value needs.
+fn reply_xattr(data: &[u8], size: u32, reply: ReplyXattr) {
+    match size {
+        0 => reply.size(data.len() as u32),
+        size if (size as usize) < data.len() => reply.error(ERANGE),
+        _ => reply.data(data),
+    }
+}
+
 impl Filesystem for KubeFuse {
-    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        println!(
-            "lookup(parent:{}, name: {})",
-            parent,
-            name.to_string_lossy()
-        );
-
-        if let Some(file) = self
-            .kube_vfs
-            .get_file_from_parent_by_name_two(parent, name.to_str().unwrap())
-        {
+    fn init(&mut self, _req: &Request, config: &mut KernelConfig) -> Result<(), c_int> {
+        // Have the kernel use readdirplus, which hands back the attributes of every entry and
+        // saves a lookup per entry when listing a directory with `ls -l`.
+        if let Err(unsupported) = config.add_capabilities(FUSE_DO_READDIRPLUS) {
+            warn!("Kernel does not support readdirplus ({:#x})", unsupported);
+        }
+        Ok(())
+    }
+
+    fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let _span = debug_span!("lookup", parent, name = %name.to_string_lossy()).entered();
+        let parent = self.inode(parent);
+        self.reload_if_requested();
+        self.cancellable_by(Some(req));
+
+        // Every file kubefs presents has a utf-8 name, so anything else can't exist.
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => return reply.error(ENOENT),
+        };
+
+        if name.contains('?') {
+            return match self.kube_vfs.get_projection_file(parent, name) {
+                Ok(Some((_, attr))) => reply.entry(&TTL, &attr, 0),
+                Ok(None) => reply.error(ENOENT),
+                Err(err) => {
+                    warn!("Unable to look up {}: {}", name, err);
+                    reply.error(EINVAL)
+                }
+            };
+        }
+
+        if name.contains('@') {
+            return match self.kube_vfs.get_versioned_file(parent, name) {
+                Ok(Some((_, attr))) => reply.entry(&TTL, &attr, 0),
+                Ok(None) => reply.error(ENOENT),
+                Err(err) => {
+                    warn!("Unable to look up {}: {}", name, err);
+                    reply.error(EINVAL)
+                }
+            };
+        }
+
+        let file = match self.kube_vfs.get_file_from_parent_by_name_two(parent, name) {
+            Ok(file) => file,
+            Err(err) => {
+                warn!("Unable to look up {}: {:#}", name, err);
+                return reply.error(error_code(&err));
+            }
+        };
+
+        if let Some(file) = file {
             let (_, attr) = file;
             reply.entry(&TTL, &attr, 0);
+        } else if name.contains(".owned.") {
+            match self.kube_vfs.get_owned_fields_file(parent, name) {
+                Ok(Some((_, attr))) => reply.entry(&TTL, &attr, 0),
+                Ok(None) => reply.error(ENOENT),
+                Err(err) => {
+                    warn!("Unable to look up {}: {}", name, err);
+                    reply.error(EINVAL)
+                }
+            }
         } else {
             reply.error(ENOENT);
         }
     }
 
-    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
-        println!("getattr(ino:{})", ino);
+    fn getattr(&mut self, req: &Request, ino: u64, reply: ReplyAttr) {
+        let _span = debug_span!("getattr", ino).entered();
+        let ino = self.inode(ino);
+        self.reload_if_requested();
+        self.cancellable_by(Some(req));
         match self.kube_vfs.get_file(ino).map(|(_, f)| f) {
             Some(attr) => reply.attr(&TTL, &attr),
             _ => reply.error(ENOENT),
@@ -85,28 +312,278 @@ impl Filesystem for KubeFuse {
 
     fn read(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         _fh: u64,
         offset: i64,
-        _size: u32,
+        size: u32,
         _flags: i32,
         _lock: Option<u64>,
         reply: ReplyData,
     ) {
-        println!(
-            "read(ino: {}, fh: {}, offset: {}, size: {}, flags: {}, lock: {:?})",
-            ino, _fh, offset, _size, _flags, _lock
-        );
+        let _span = debug_span!(
+            "read",
+            ino,
+            fh = _fh,
+            offset,
+            size,
+            flags = _flags,
+            lock = ?_lock
+        )
+        .entered();
+        let ino = self.inode(ino);
+        self.cancellable_by(Some(req));
+
+        match self.kube_vfs.read_file(ino) {
+            // Contents change between reads, and files may report a size they don't have, so
+            // reads past the end are normal and get nothing back.
+            Ok(contents) => {
+                let contents = contents.get(offset as usize..).unwrap_or(&[]);
+                reply.data(&contents[..contents.len().min(size as usize)])
+            }
+            Err(err) => {
+                warn!("Unable to read {}: {:#}", ino, err);
+                reply.error(error_code(&err))
+            }
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let _span = debug_span!("setattr", ino, size = ?size).entered();
+        let ino = self.inode(ino);
+        self.cancellable_by(None);
+
+        if let Some(size) = size {
+            if !self.kube_vfs.is_writable(ino) {
+                return reply.error(EACCES);
+            }
+
+            if let Err(err) = self.kube_vfs.truncate_file(ino, size) {
+                warn!("Unable to truncate {}: {}", ino, err);
+                return reply.error(EIO);
+            }
+        } else if atime.is_some() || mtime.is_some() {
+            if let Err(err) = self.kube_vfs.touch_file(ino) {
+                warn!("Unable to refresh inode {}: {:#}", ino, err);
+                return reply.error(error_code(&err));
+            }
+        }
+
+        match self.kube_vfs.get_file(ino).map(|(_, f)| f) {
+            Some(mut attr) => {
+                if let Some(size) = size {
+                    attr.size = size;
+                }
+                reply.attr(&TTL, &attr)
+            }
+            _ => reply.error(ENOENT),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let _span = debug_span!("write", ino, offset, size = data.len()).entered();
+        let ino = self.inode(ino);
+
+        if !self.kube_vfs.is_writable(ino) {
+            return reply.error(EACCES);
+        }
+
+        match self.kube_vfs.write_file(ino, offset, data) {
+            Ok(written) => reply.written(written as u32),
+            Err(err) => {
+                warn!("Unable to write {}: {}", ino, err);
+                reply.error(EIO)
+            }
+        }
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        _flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let _span = debug_span!("create", parent, name = %name.to_string_lossy()).entered();
+        let parent = self.inode(parent);
 
-        match self.kube_vfs.get_kube_manifest(ino) {
-            Ok(contents) => reply.data(&contents.as_bytes()[offset as usize..]),
-            Err(_) => reply.error(ENOENT),
+        // Names in the cluster are utf-8, so there is nothing else to create.
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => return reply.error(EINVAL),
+        };
+
+        match self.kube_vfs.create_file(parent, name) {
+            Ok((_, attr)) => reply.created(&TTL, &attr, 0, 0, 0),
+            Err(err) => {
+                warn!("Unable to create {}: {}", name, err);
+                reply.error(error_code(&err))
+            }
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let _span = debug_span!("rmdir", parent, name = %name.to_string_lossy()).entered();
+        let parent = self.inode(parent);
+        self.cancellable_by(None);
+
+        // Every directory kubefs presents has a utf-8 name, so anything else can't exist.
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => return reply.error(ENOENT),
+        };
+
+        match self.kube_vfs.remove_directory(parent, name) {
+            Ok(()) => reply.ok(),
+            Err(err) => {
+                warn!("Unable to remove {}: {:#}", name, err);
+                reply.error(error_code(&err))
+            }
+        }
+    }
+
+    fn flush(&mut self, _req: &Request, ino: u64, _fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        let _span = debug_span!("flush", ino).entered();
+        let ino = self.inode(ino);
+        self.cancellable_by(None);
+        match self.kube_vfs.flush_file(ino) {
+            Ok(_) => reply.ok(),
+            Err(err) => {
+                warn!("Unable to flush {}: {}", ino, err);
+                reply.error(error_code(&err))
+            }
+        }
+    }
+
+    fn lseek(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        whence: i32,
+        reply: ReplyLseek,
+    ) {
+        let _span = debug_span!("lseek", ino, offset, whence).entered();
+        let ino = self.inode(ino);
+        self.reload_if_requested();
+        self.cancellable_by(Some(req));
+
+        // Only the files read past the page cache report a made up size, those have to be
+        // rendered to know where they end.
+        let size = match self.kube_vfs.is_direct_io(ino) {
+            true => match self.kube_vfs.read_file(ino) {
+                Ok(contents) => contents.len() as i64,
+                Err(err) => return reply.error(error_code(&err)),
+            },
+            false => match self.kube_vfs.get_file(ino) {
+                Some((_, attr)) => attr.size as i64,
+                None => return reply.error(ENOENT),
+            },
+        };
+
+        // The files are all data and the only hole is the implicit one at the end of the file.
+        match whence {
+            SEEK_SET if offset < 0 => reply.error(EINVAL),
+            SEEK_SET => reply.offset(offset),
+            SEEK_END if size + offset < 0 => reply.error(EINVAL),
+            SEEK_END => reply.offset(size + offset),
+            SEEK_DATA | SEEK_HOLE if offset < 0 || offset >= size => reply.error(ENXIO),
+            SEEK_DATA => reply.offset(offset),
+            SEEK_HOLE => reply.offset(size),
+            _ => reply.error(EINVAL),
+        }
+    }
+
+    fn statfs(&mut self, _req: &Request, _ino: u64, reply: ReplyStatfs) {
+        let _span = debug_span!("statfs").entered();
+        // There is no space to speak of, but tools size their reads on the block size reported
+        // here as well, so it has to match the one of the files.
+        let blksize = self.kube_vfs.block_size();
+        reply.statfs(0, 0, 0, 0, 0, blksize, 255, blksize);
+    }
+
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        let _span = debug_span!("readlink", ino).entered();
+        let ino = self.inode(ino);
+        match self.kube_vfs.read_link(ino) {
+            Some(target) => reply.data(target.as_bytes()),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getxattr(&mut self, _req: &Request, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
+        let _span = debug_span!("getxattr", ino, ?name).entered();
+        let ino = self.inode(ino);
+        match name
+            .to_str()
+            .and_then(|name| self.kube_vfs.get_xattr(ino, name))
+        {
+            Some(value) => reply_xattr(value.as_bytes(), size, reply),
+            None => reply.error(ENODATA),
+        }
+    }
+
+    fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: ReplyXattr) {
+        let _span = debug_span!("listxattr", ino).entered();
+        let ino = self.inode(ino);
+        let mut names = Vec::new();
+        for name in self.kube_vfs.list_xattrs(ino) {
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        reply_xattr(&names, size, reply);
+    }
+
+    fn open(&mut self, req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
+        let _span = debug_span!("open", ino, flags).entered();
+        let ino = self.inode(ino);
+        self.reload_if_requested();
+        self.cancellable_by(Some(req));
+        match self.kube_vfs.open_file(ino) {
+            Ok(()) if self.kube_vfs.is_direct_io(ino) => reply.opened(0, FOPEN_DIRECT_IO),
+            Ok(()) => reply.opened(0, 0),
+            Err(err) => {
+                warn!("Unable to refresh inode {}: {:#}", ino, err);
+                reply.error(error_code(&err));
+            }
         }
     }
 
     fn opendir(&mut self, _req: &Request, ino: u64, _flags: i32, reply: ReplyOpen) {
-        println!("opendir(ino: {}, flags: {})", ino, _flags);
+        let _span = debug_span!("opendir", ino, flags = _flags).entered();
+        let ino = self.inode(ino);
         match self.kube_vfs.get_file(ino) {
             Some(attr) => reply.opened(0, attr.1.flags),
             _ => reply.error(ENOENT),
@@ -115,14 +592,25 @@ impl Filesystem for KubeFuse {
 
     fn readdir(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         _fh: u64,
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
-        println!("readdir(ino: {}, fh: {}, offset: {})", ino, _fh, offset);
-        if let Some(files) = self.kube_vfs.list_files_two(ino) {
+        let _span = debug_span!("readdir", ino, fh = _fh, offset).entered();
+        let ino = self.inode(ino);
+        self.reload_if_requested();
+        self.cancellable_by(Some(req));
+        let files = match self.kube_vfs.list_files_two(ino) {
+            Ok(files) => files,
+            Err(err) => {
+                warn!("Unable to list {}: {:#}", ino, err);
+                return reply.error(error_code(&err));
+            }
+        };
+
+        if let Some(files) = files {
             for (i, (name, file)) in files.iter().enumerate().skip(offset as usize) {
                 if reply.add(file.ino, offset + (i) as i64 + 1, file.kind, name) {
                     break;
@@ -135,4 +623,52 @@ impl Filesystem for KubeFuse {
 
         reply.error(ENOENT);
     }
+
+    fn readdirplus(
+        &mut self,
+        req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectoryPlus,
+    ) {
+        let _span = debug_span!("readdirplus", ino, fh = _fh, offset).entered();
+        let ino = self.inode(ino);
+        self.reload_if_requested();
+        self.cancellable_by(Some(req));
+        let files = match self.kube_vfs.list_files_two(ino) {
+            Ok(files) => files,
+            Err(err) => {
+                warn!("Unable to list {}: {:#}", ino, err);
+                return reply.error(error_code(&err));
+            }
+        };
+
+        if let Some(files) = files {
+            for (i, (name, file)) in files.iter().enumerate().skip(offset as usize) {
+                if reply.add(file.ino, (i + 1) as i64, name, &TTL, file, 0) {
+                    break;
+                }
+            }
+
+            reply.ok();
+            return;
+        }
+
+        reply.error(ENOENT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_escaped_mount_points() {
+        let mounts = "proc /proc proc rw,nosuid 0 0\n\
+                      kubefs /mnt/my\\040cluster fuse rw,nosuid,nodev 0 0\n";
+
+        assert!(is_listed_in_mounts(mounts, Path::new("/mnt/my cluster")));
+        assert!(!is_listed_in_mounts(mounts, Path::new("/mnt/my")));
+    }
 }
@@ -1,14 +1,25 @@
 use std::env;
 
 use crate::fuse::KubeFuse;
-use clap::{Arg, Command};
+use crate::options::{ConfigFile, KubeFsOptions, LogFormat};
+use anyhow::Context;
+use clap::{Arg, ArgAction, Command};
 use fuser::MountOption;
+use tracing::warn;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
 
 mod client;
 mod fuse;
+mod options;
 mod tree;
 mod vfs;
 
+/// Version of kubefs itself, as set in Cargo.toml.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Environment variable holding default mount options, e.g. for fstab or systemd mounts.
+const OPTIONS_ENV: &str = "KUBEFS_OPTIONS";
+
 fn get_prog_name() -> Option<String> {
     env::current_exe()
         .map_or_else(
@@ -21,30 +32,79 @@ fn get_prog_name() -> Option<String> {
         })
 }
 
+/// Sets up the log output. Closing the span of a filesystem operation logs how long it took.
+fn init_logging(options: &KubeFsOptions) -> anyhow::Result<()> {
+    let filter = EnvFilter::try_new(&options.log_level)
+        .with_context(|| format!("Invalid log level {}", options.log_level))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+
+    match options.log_format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    }
+    .map_err(anyhow::Error::msg)?;
+
+    for option in &options.unknown {
+        warn!("Ignoring unknown option {}", option);
+    }
+
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     let matches = Command::new("mount.kubefs")
-        .version("1.0.0")
+        .version(VERSION)
         .author("Nick Van Dyck")
         .about("Your Kubernetes cluster moutned as a file system. Because why not?")
-        .arg(Arg::new("namespace"))
+        .arg(Arg::new("root").help("Part of the cluster to mount, e.g. `default/pods`"))
         .arg(Arg::new("mountpoint"))
-        .arg(Arg::new("options").short('o').required(false))
+        .arg(
+            Arg::new("options")
+                .short('o')
+                .help("Mount options, override the ones in KUBEFS_OPTIONS and the config file")
+                .required(false)
+                .action(ArgAction::Append),
+        )
         .get_matches();
 
     let mount_point = matches.get_one::<String>("mountpoint").expect("required");
+    // Options in KUBEFS_OPTIONS go first so the ones passed with -o win: built-in defaults are
+    // overridden by the config file, then the environment, then the command line.
+    let env_options = env::var(OPTIONS_ENV).unwrap_or_default();
+    let mut kube_options = ConfigFile::load()?.options()?.merge(
+        std::iter::once(env_options.as_str()).chain(
+            matches
+                .get_many::<String>("options")
+                .unwrap_or_default()
+                .map(|o| o.as_str()),
+        ),
+    )?;
+    kube_options.root = matches.get_one::<String>("root").cloned();
+    init_logging(&kube_options)?;
 
-    let options = vec![
-        MountOption::RO,
-        MountOption::FSName("kubefs".to_string()),
+    // With -o touch_refresh it's mounted read-write even without -o rw, so `touch` reaches
+    // kubefs to refresh a manifest. Every write is still refused by the filesystem itself then.
+    let mut options = vec![
+        if kube_options.read_write || kube_options.touch_refresh {
+            MountOption::RW
+        } else {
+            MountOption::RO
+        },
+        MountOption::FSName(kube_options.fsname.clone()),
         // MountOption::AutoUnmount,
         // MountOption::AllowRoot,
         // MountOption::AllowOther,
         MountOption::NoExec,
         MountOption::NoSuid,
     ];
+    if let Some(subtype) = &kube_options.subtype {
+        options.push(MountOption::Subtype(subtype.clone()));
+    }
 
     return match get_prog_name() == Some("mount.kubefs".into()) {
-        true => KubeFuse::mount_as_daemon(mount_point, &options),
-        false => KubeFuse::mount(mount_point, &options),
+        true => KubeFuse::mount_as_daemon(mount_point, &options, &kube_options),
+        false => KubeFuse::mount(mount_point, &options, &kube_options),
     };
 }
@@ -0,0 +1,699 @@
+use std::{collections::HashMap, env, fs, io, path::PathBuf, str::FromStr, time::Duration};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Smallest block size `blksize` accepts, and the one used without it.
+const MIN_BLKSIZE: u32 = 512;
+
+/// Largest block size `blksize` accepts, the kernel doesn't read ahead further than this anyway.
+const MAX_BLKSIZE: u32 = 1024 * 1024;
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human readable lines.
+    #[default]
+    Text,
+    /// One json object per line with the fields as keys, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow::Error::msg(format!(
+                "Unknown log format {}, expected text or json",
+                s
+            ))),
+        }
+    }
+}
+
+/// Serialization of the manifest files.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KubeManifestType {
+    Json,
+    #[default]
+    Yaml,
+}
+
+impl FromStr for KubeManifestType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(KubeManifestType::Json),
+            "yaml" | "yml" => Ok(KubeManifestType::Yaml),
+            _ => Err(anyhow::Error::msg(format!(
+                "Unknown format {}, expected json or yaml",
+                s
+            ))),
+        }
+    }
+}
+
+/// Whether json manifests are pretty printed or minified.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KubeJsonStyle {
+    #[default]
+    Pretty,
+    Compact,
+}
+
+impl FromStr for KubeJsonStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(KubeJsonStyle::Pretty),
+            "compact" => Ok(KubeJsonStyle::Compact),
+            _ => Err(anyhow::Error::msg(format!(
+                "Unknown json style {}, expected pretty or compact",
+                s
+            ))),
+        }
+    }
+}
+
+/// How the levels of the tree are nested underneath the mount point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// The mount point is the current context: `<namespace>/<kind>/<resource>` alongside cluster
+    /// level files like `cluster_info` and the namespace manifests.
+    #[default]
+    Context,
+    /// Only the namespace directories: `<namespace>/<kind>/<resource>`.
+    Namespace,
+    /// Kind first, handy to look at a kind across namespaces: `<kind>/<namespace>/<resource>`.
+    Kind,
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "context" => Ok(Layout::Context),
+            "namespace" => Ok(Layout::Namespace),
+            "kind" => Ok(Layout::Kind),
+            _ => Err(anyhow::Error::msg(format!(
+                "Unknown layout {}, expected one of context, namespace or kind",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KubeFsOptions {
+    /// Bypass the response cache and always query the api server.
+    pub no_cache: bool,
+    /// How long a cached listing is served before it's fetched again, `cache_ttl=30s`. Listings
+    /// are kept until evicted otherwise.
+    pub cache_ttl: Option<Duration>,
+    /// Maximum number of listings kept in the cache, `cache_size=1000`.
+    pub cache_size: Option<u64>,
+    /// Keeps the most browsed listings warm, fetching them again shortly before their
+    /// `cache_ttl` runs out. `prefetch` does so for the 10 most browsed ones, `prefetch=20` for
+    /// the 20 most browsed. Does nothing without a `cache_ttl`, or with `watch`, which keeps the
+    /// listings current already.
+    pub prefetch: Option<usize>,
+    /// Fetch a resource again when its manifest is opened, so the file being read is current
+    /// even when the listing it's part of is cached.
+    pub refresh_on_open: bool,
+    /// Mount read-write even without `rw`, so `touch` on a manifest reaches kubefs and fetches
+    /// the resource again. Every write is still refused. Set with `touch_refresh`.
+    pub touch_refresh: bool,
+    /// Watch listed resources so cached listings follow changes in the cluster.
+    pub watch: bool,
+    /// Serve the kind directories of `~get` from an informer per kind, a listing across every
+    /// namespace that gets watched rather than listed again. Set with `informers`.
+    pub informers: bool,
+    /// How long until watched listings are evicted and listed again, `resync=5m`.
+    pub resync: Option<Duration>,
+    /// Render the gzipped release stored in Helm release secrets instead of the encoded blob.
+    pub decompress: bool,
+    /// Don't list a kind until its directory is read, lookups fetch single resources instead.
+    pub lazy: bool,
+    /// Leave out the kinds a namespace has no resources of.
+    pub hide_empty: bool,
+    /// Leave out `kube-system` and the other namespaces of the cluster itself, on by default.
+    /// Mount with `show_system_namespaces` to see them.
+    pub hide_system_namespaces: bool,
+    /// Namespaces to leave out besides the system ones, `hide_namespace=<name>` adds one.
+    pub hidden_namespaces: Vec<String>,
+    /// Api groups whose kinds are served, `core` being the group of pods and services. Set with
+    /// `api_groups=core:apps:batch`.
+    pub api_groups: Vec<String>,
+    /// Verbs a kind has to support to show up, next to `list` which every kind needs. Set with
+    /// `verbs=list:watch`, or `verbs=patch` to only see the kinds that can be written to.
+    pub verbs: Vec<String>,
+    /// Short names kinds can be mounted by, e.g. `svc` for services, keyed by the lowercased kind.
+    /// They only apply to the kind in the root argument, `default/svc`, the kind directories keep
+    /// their plural names.
+    pub aliases: HashMap<String, String>,
+    /// Put the kinds of a namespace in a directory per api group, `apps/deployments` rather than
+    /// `deployments`. Kinds of the core group, like pods, stay where they are.
+    pub group_by_apigroup: bool,
+    /// Split the pods and workloads of a namespace into `ready` and `not-ready` directories on
+    /// top of listing them, set with `health_split`.
+    pub health_split: bool,
+    /// Put a `README` in every kind directory, describing the kind and how many resources of it
+    /// there are, for file managers that hide dotfiles. Set with `readme`.
+    pub readme: bool,
+    /// Put an `.explain` in every kind directory documenting its fields, like `kubectl explain`.
+    /// Needs a cluster serving OpenAPI v3, the file is empty otherwise. Set with `explain`.
+    pub explain: bool,
+    /// Leave the manifests out, so resources only show up through their directories and the
+    /// files next to them, like the summary, events and logs of pods. The `.diff` files and
+    /// `~get`, which only hold manifests, go as well. Projections like `<name>.yml?<path>` still
+    /// look up. Set with `hide_manifest`.
+    pub hide_manifest: bool,
+    /// Show the extra columns of `kubectl get -o wide` in the `.table` files, like the node and
+    /// ip of pods.
+    pub wide: bool,
+    /// Fail listings and lookups with EIO when the api server can't be reached or refuses,
+    /// instead of showing what was listed before. Lets scripts tell an empty directory apart
+    /// from one that couldn't be fetched.
+    pub strict: bool,
+    /// Prefix every line of the pod `logs` files with its timestamp.
+    pub log_timestamps: bool,
+    /// Only show pod logs this recent, `log_since=1h`.
+    pub log_since: Option<Duration>,
+    /// Mount read-write, allowing edits to be written back to the cluster.
+    pub read_write: bool,
+    /// How often an apply that conflicts with a newer version of the object is retried,
+    /// `write_retries=3`. See `KubeClient::apply_resource`.
+    pub write_retries: u32,
+    /// Field manager writes are made as, `field_manager=kubefs-alice`. Server side applies own
+    /// the fields they set under this name, and it shows up in the managedFields of anything
+    /// kubefs changed. Defaults to `kubefs`.
+    pub field_manager: String,
+    /// Let `rmdir` on a namespace directory delete the namespace along with everything in it,
+    /// `allow_recursive_delete`. Only once a `.confirm` file was created in the namespace first.
+    pub allow_recursive_delete: bool,
+    /// Kinds that may be written to, by plural or kind name, `rw=configmaps:secrets`. Repeating
+    /// the option adds to the kinds, `rw=configmaps,rw=secrets`. Every kind is writable when empty.
+    pub writable_kinds: Vec<String>,
+    /// How the levels of the tree are nested, set through `layout=context|namespace|kind`.
+    pub layout: Layout,
+    /// Path style `[context/]namespace[/kind]` argument to only mount part of the cluster.
+    pub root: Option<String>,
+    /// Without a `root` argument, mount the namespace the current context points at rather than
+    /// the whole cluster, like kubectl picks the namespace to work in. Set with `current_namespace`.
+    pub current_namespace: bool,
+    /// Name the mount shows up with in `mount`, `df` and `/proc/mounts`, `fsname=prod-cluster`.
+    /// Defaults to `kubefs`.
+    pub fsname: String,
+    /// Filesystem subtype reported next to the `fuse` type, `subtype=kubefs`.
+    pub subtype: Option<String>,
+    /// Serialization used for the manifest files, `format=yaml|json`.
+    pub format: KubeManifestType,
+    /// Whether json manifests are pretty printed or minified, `json=pretty|compact`.
+    pub json_style: KubeJsonStyle,
+    /// Suffix of yaml manifest files, `ext=yml|yaml`. Json manifests always end in `.json`.
+    pub extension: String,
+    /// Block size the files report, `blksize=131072`. Larger blocks let the kernel read big logs
+    /// and manifests in fewer round trips. A power of two between 512 bytes and 1MiB.
+    pub blksize: u32,
+    /// Cut manifests off after this many bytes, `max_manifest_bytes=1048576`, for editors and
+    /// tools that choke on huge files. A marker at the end tells the manifest got truncated.
+    pub max_manifest_bytes: Option<usize>,
+    /// Seconds to wait for a connection to the api server, `connect_timeout=5`. Waits for as long
+    /// as the OS allows when not set.
+    pub connect_timeout: Option<Duration>,
+    /// Timeout of list and watch calls, `list_timeout=60s`. Must be 1s or more, the server only
+    /// takes whole seconds, and below 295s. Watches pass it on to the server, list calls are
+    /// bounded by it per page, and a page that takes longer is listed again in smaller pages.
+    pub list_timeout: Option<Duration>,
+    /// Upper bound of the delay between retries of a failed request, `max_backoff=5s`.
+    pub max_backoff: Duration,
+    /// How long an idle connection to the api server is kept around for reuse,
+    /// `pool_idle_timeout=90s`. Longer saves handshakes when requests come in bursts, shorter
+    /// frees up connections on busy api servers sooner. Not set by default, hyper then keeps them
+    /// for 90s.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Maximum number of idle connections kept to the api server, `pool_max_idle=8`. Listing
+    /// many kinds at once opens a connection each, keeping them makes the next round faster at
+    /// the cost of holding on to them. Unlimited when not set.
+    pub pool_max_idle: Option<usize>,
+    /// Rebuild the client whenever the kubeconfig changes, e.g. after switching contexts.
+    pub reload_kubeconfig: bool,
+    /// Cluster of the kubeconfig to connect to instead of the one of the current context,
+    /// `cluster=<name>`, like `kubectl --cluster`.
+    pub cluster: Option<String>,
+    /// User of the kubeconfig to authenticate as instead of the one of the current context,
+    /// `user=<name>`, like `kubectl --user`.
+    pub user: Option<String>,
+    /// Url of the api server to connect to instead of the one in the kubeconfig, `server=<url>`.
+    pub server: Option<String>,
+    /// Bearer token used to authenticate against `server`, `token=<token>`.
+    pub token: Option<String>,
+    /// Path to the certificate authority of `server`, `ca=<path>`.
+    pub ca: Option<String>,
+    /// Skip verifying the certificate of `server`.
+    pub insecure_skip_tls_verify: bool,
+    /// Working directory of the daemon, `workdir=/`. Defaults to `/` so the daemon doesn't keep
+    /// another file system busy.
+    pub workdir: String,
+    /// Octal umask of the daemon, `umask=027`.
+    pub umask: u32,
+    /// First inode number handed out, `inode_base=1000000`. Keeps the inodes of mounts that are
+    /// nested or used side by side apart for tools that don't tell them apart by device.
+    pub inode_base: u64,
+    /// Format of the log lines, `log_format=text|json`.
+    pub log_format: LogFormat,
+    /// Which log lines are written, `log_level=debug` or a directive like `log_level=kubefs=trace`.
+    /// Every filesystem operation is logged with its duration at the debug level.
+    pub log_level: String,
+    /// Options that weren't recognized, they are reported once logging is set up.
+    pub unknown: Vec<String>,
+}
+
+impl Default for KubeFsOptions {
+    fn default() -> Self {
+        KubeFsOptions {
+            no_cache: false,
+            cache_ttl: None,
+            cache_size: None,
+            prefetch: None,
+            refresh_on_open: false,
+            touch_refresh: false,
+            watch: false,
+            informers: false,
+            resync: None,
+            decompress: false,
+            lazy: false,
+            hide_empty: false,
+            hide_system_namespaces: true,
+            hidden_namespaces: Vec::new(),
+            api_groups: vec![
+                String::new(),
+                String::from("apps"),
+                String::from("batch"),
+                String::from("networking.k8s.io"),
+                String::from("rbac.authorization.k8s.io"),
+            ],
+            verbs: Vec::new(),
+            aliases: HashMap::from([
+                (String::from("service"), String::from("svc")),
+                (String::from("deployment"), String::from("deploy")),
+            ]),
+            group_by_apigroup: false,
+            health_split: false,
+            readme: false,
+            explain: false,
+            hide_manifest: false,
+            wide: false,
+            strict: false,
+            log_timestamps: false,
+            log_since: None,
+            read_write: false,
+            write_retries: 3,
+            field_manager: String::from("kubefs"),
+            allow_recursive_delete: false,
+            writable_kinds: Vec::new(),
+            layout: Layout::default(),
+            root: None,
+            current_namespace: false,
+            fsname: String::from("kubefs"),
+            subtype: None,
+            format: KubeManifestType::default(),
+            json_style: KubeJsonStyle::default(),
+            extension: String::from("yml"),
+            blksize: MIN_BLKSIZE,
+            max_manifest_bytes: None,
+            connect_timeout: None,
+            max_backoff: Duration::from_secs(5),
+            list_timeout: None,
+            pool_idle_timeout: None,
+            pool_max_idle: None,
+            reload_kubeconfig: false,
+            cluster: None,
+            user: None,
+            server: None,
+            token: None,
+            ca: None,
+            insecure_skip_tls_verify: false,
+            workdir: String::from("/"),
+            umask: 0o027,
+            inode_base: 1,
+            log_format: LogFormat::default(),
+            log_level: String::from("info"),
+            unknown: Vec::new(),
+        }
+    }
+}
+
+impl KubeFsOptions {
+    /// Parses the values passed through `-o` on top of these options, each of which can hold a
+    /// comma separated list of `key` or `key=value` pairs, the same way mount(8) hands them over.
+    /// When an option is given more than once the last one wins, `rw=<kind>` adds to the kinds
+    /// set before it.
+    pub fn merge<'a>(self, values: impl Iterator<Item = &'a str>) -> anyhow::Result<Self> {
+        let mut options = self;
+
+        for option in values.flat_map(|v| v.split(',')).filter(|o| !o.is_empty()) {
+            let (key, value) = match option.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (option, None),
+            };
+
+            match key {
+                "no_cache" => options.no_cache = true,
+                "cache_ttl" => options.cache_ttl = Some(parse_duration(key, value)?),
+                "cache_size" => options.cache_size = Some(parse_value(key, value)?),
+                "prefetch" => {
+                    options.prefetch = Some(match value {
+                        Some(_) => parse_value(key, value)?,
+                        None => DEFAULT_PREFETCH,
+                    })
+                }
+                "refresh_on_open" => options.refresh_on_open = true,
+                "touch_refresh" => options.touch_refresh = true,
+                "watch" => options.watch = true,
+                "informers" => options.informers = true,
+                "resync" => {
+                    let interval = parse_duration(key, value)?;
+                    if interval.is_zero() {
+                        return Err(anyhow::Error::msg("Option resync must be above 0"));
+                    }
+                    options.resync = Some(interval);
+                }
+                "decompress" => options.decompress = true,
+                "lazy" => options.lazy = true,
+                "hide_empty" => options.hide_empty = true,
+                "hide_system_namespaces" => options.hide_system_namespaces = true,
+                "show_system_namespaces" => options.hide_system_namespaces = false,
+                "hide_namespace" => options.hidden_namespaces.push(parse_value(key, value)?),
+                "api_groups" => {
+                    let groups: String = parse_value(key, value)?;
+                    options.api_groups = groups.split(':').map(api_group).collect();
+                }
+                "verbs" => {
+                    let verbs: String = parse_value(key, value)?;
+                    options.verbs = verbs.split(':').map(String::from).collect();
+                    if let Some(verb) = options.verbs.iter().find(|v| !VERBS.contains(&v.as_str()))
+                    {
+                        return Err(anyhow::Error::msg(format!(
+                            "Unknown verb {}, expected one of {}",
+                            verb,
+                            VERBS.join(", ")
+                        )));
+                    }
+                }
+                "group_by_apigroup" => options.group_by_apigroup = true,
+                "health_split" => options.health_split = true,
+                "readme" => options.readme = true,
+                "explain" => options.explain = true,
+                "hide_manifest" => options.hide_manifest = true,
+                "wide" => options.wide = true,
+                "strict" => options.strict = true,
+                "log_timestamps" => options.log_timestamps = true,
+                "log_since" => options.log_since = Some(parse_duration(key, value)?),
+                "rw" => {
+                    options.read_write = true;
+                    if let Some(kinds) = value {
+                        let kinds = kinds.split(':').filter(|k| !k.is_empty());
+                        options
+                            .writable_kinds
+                            .extend(kinds.map(|k| k.to_lowercase()));
+                    }
+                }
+                "write_retries" => options.write_retries = parse_value(key, value)?,
+                "field_manager" => options.field_manager = parse_value(key, value)?,
+                "allow_recursive_delete" => options.allow_recursive_delete = true,
+                "ro" => {
+                    options.read_write = false;
+                    options.writable_kinds.clear();
+                }
+                "current_namespace" => options.current_namespace = true,
+                "fsname" => options.fsname = parse_value(key, value)?,
+                "subtype" => options.subtype = Some(parse_value(key, value)?),
+                "layout" => options.layout = parse_value(key, value)?,
+                "format" => options.format = parse_value(key, value)?,
+                "json" => options.json_style = parse_value(key, value)?,
+                "ext" => {
+                    options.extension = match value {
+                        Some(ext @ ("yml" | "yaml")) => ext.into(),
+                        _ => return Err(anyhow::Error::msg("Option ext expects yml or yaml")),
+                    }
+                }
+                "max_manifest_bytes" => options.max_manifest_bytes = Some(parse_value(key, value)?),
+                "blksize" => {
+                    let size: u32 = parse_value(key, value)?;
+                    if !size.is_power_of_two() {
+                        return Err(anyhow::Error::msg("Option blksize must be a power of two"));
+                    }
+                    options.blksize = size.clamp(MIN_BLKSIZE, MAX_BLKSIZE);
+                }
+                "connect_timeout" => {
+                    options.connect_timeout = Some(Duration::from_secs(parse_value(key, value)?))
+                }
+                "list_timeout" => {
+                    let timeout = parse_duration(key, value)?;
+                    // The server takes whole seconds, where 0 means no timeout at all.
+                    if timeout < Duration::from_secs(1) || timeout >= Duration::from_secs(295) {
+                        return Err(anyhow::Error::msg(
+                            "Option list_timeout must be at least 1s and below 295s",
+                        ));
+                    }
+                    options.list_timeout = Some(timeout);
+                }
+                "max_backoff" => options.max_backoff = parse_duration(key, value)?,
+                "pool_idle_timeout" => {
+                    options.pool_idle_timeout = Some(parse_duration(key, value)?)
+                }
+                "pool_max_idle" => options.pool_max_idle = Some(parse_value(key, value)?),
+                "reload_kubeconfig" => options.reload_kubeconfig = true,
+                "cluster" => options.cluster = Some(parse_value(key, value)?),
+                "user" => options.user = Some(parse_value(key, value)?),
+                "server" => options.server = Some(parse_value(key, value)?),
+                "token" => options.token = Some(parse_value(key, value)?),
+                "ca" => options.ca = Some(parse_value(key, value)?),
+                "insecure_skip_tls_verify" => options.insecure_skip_tls_verify = true,
+                "workdir" => options.workdir = parse_value(key, value)?,
+                "umask" => {
+                    let umask: String = parse_value(key, value)?;
+                    options.umask = u32::from_str_radix(&umask, 8).map_err(|_| {
+                        anyhow::Error::msg(format!(
+                            "Option umask expects an octal mode, not {}",
+                            umask
+                        ))
+                    })?;
+                }
+                "inode_base" => {
+                    options.inode_base = parse_value(key, value)?;
+                    if options.inode_base == 0 {
+                        return Err(anyhow::Error::msg("Option inode_base must be at least 1"));
+                    }
+                }
+                "log_format" => options.log_format = parse_value(key, value)?,
+                "log_level" => options.log_level = parse_value(key, value)?,
+                _ => options.unknown.push(option.into()),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Defaults read from the config file, `~/.config/kubefs/config.yaml` unless `XDG_CONFIG_HOME`
+/// says otherwise. Options from `KUBEFS_OPTIONS` and `-o` are applied on top, so they win.
+///
+/// ```yaml
+/// aliases:
+///   statefulset: sts
+/// format: json
+/// cache_ttl: 30s
+/// hidden_namespaces: [monitoring]
+/// api_groups: [core, apps, cert-manager.io]
+/// options: [watch, wide]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Added to the built-in aliases, replacing them for the same kind. Like those they only
+    /// apply to the root argument.
+    pub aliases: HashMap<String, String>,
+    pub format: Option<String>,
+    pub cache_ttl: Option<String>,
+    pub hidden_namespaces: Vec<String>,
+    /// Replaces the built-in api groups when set.
+    pub api_groups: Option<Vec<String>>,
+    /// Any other mount options, written like they are passed to `-o`.
+    pub options: Vec<String>,
+}
+
+impl ConfigFile {
+    pub fn path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("kubefs").join("config.yaml"))
+    }
+
+    /// Reads the config file, an empty one when there is none.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = match ConfigFile::path() {
+            Some(path) => path,
+            None => return Ok(ConfigFile::default()),
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(ConfigFile::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Unable to read {}", path.display()))
+            }
+        };
+
+        // An empty file is null rather than an empty mapping as far as yaml is concerned.
+        if text.trim().is_empty() {
+            return Ok(ConfigFile::default());
+        }
+
+        serde_yaml::from_str(&text).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    /// The defaults with the settings of the config file applied. The format and cache ttl go
+    /// through the same parsing as their `-o` counterparts.
+    pub fn options(&self) -> anyhow::Result<KubeFsOptions> {
+        let mut options = KubeFsOptions::default();
+        options.aliases.extend(
+            self.aliases
+                .iter()
+                .map(|(kind, alias)| (kind.to_lowercase(), alias.clone())),
+        );
+        options.hidden_namespaces = self.hidden_namespaces.clone();
+        if let Some(groups) = &self.api_groups {
+            options.api_groups = groups.iter().map(|g| api_group(g)).collect();
+        }
+
+        let format = self.format.as_ref().map(|f| format!("format={}", f));
+        let cache_ttl = self.cache_ttl.as_ref().map(|t| format!("cache_ttl={}", t));
+
+        options.merge(
+            format
+                .iter()
+                .chain(cache_ttl.iter())
+                .chain(self.options.iter())
+                .map(String::as_str),
+        )
+    }
+}
+
+/// Number of listings kept warm by a plain `prefetch`.
+const DEFAULT_PREFETCH: usize = 10;
+
+/// Verbs the api server advertises in discovery.
+const VERBS: [&str; 8] = [
+    "get",
+    "list",
+    "watch",
+    "create",
+    "update",
+    "patch",
+    "delete",
+    "deletecollection",
+];
+
+/// The core group has no name, `core` is accepted for it since an empty one is easy to miss.
+fn api_group(group: &str) -> String {
+    match group {
+        "core" => String::new(),
+        group => group.into(),
+    }
+}
+
+/// Parses durations like `90s`, `30m` or `1h`, plain numbers are taken as seconds.
+fn parse_duration(key: &str, value: Option<&str>) -> anyhow::Result<Duration> {
+    let value: String = parse_value(key, value)?;
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+
+    let number: u64 = number.parse().map_err(|_| {
+        anyhow::Error::msg(format!("Invalid duration {} for option {}", value, key))
+    })?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 60 * 60)),
+        _ => Err(anyhow::Error::msg(format!(
+            "Invalid duration {} for option {}, expected a unit of ms, s, m or h",
+            value, key
+        ))),
+    }
+}
+
+fn parse_value<T>(key: &str, value: Option<&str>) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = value.ok_or(anyhow::Error::msg(format!(
+        "Option {} requires a value",
+        key
+    )))?;
+
+    value.parse().map_err(|e| {
+        anyhow::Error::msg(format!("Invalid value {} for option {}: {}", value, key, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(values: &[&str]) -> anyhow::Result<KubeFsOptions> {
+        KubeFsOptions::default().merge(values.iter().copied())
+    }
+
+    #[test]
+    fn parses_the_resync_interval() {
+        assert_eq!(
+            parse(&["resync=5m"]).unwrap().resync,
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            parse(&["resync=90"]).unwrap().resync,
+            Some(Duration::from_secs(90))
+        );
+        assert!(parse(&["resync=0"]).is_err());
+        assert!(parse(&["resync=0s"]).is_err());
+    }
+
+    #[test]
+    fn bounds_the_list_timeout() {
+        assert_eq!(
+            parse(&["list_timeout=1s"]).unwrap().list_timeout,
+            Some(Duration::from_secs(1))
+        );
+        assert!(parse(&["list_timeout=500ms"]).is_err());
+        assert!(parse(&["list_timeout=0"]).is_err());
+        assert!(parse(&["list_timeout=295s"]).is_err());
+    }
+
+    #[test]
+    fn collects_the_writable_kinds() {
+        let options = parse(&["rw=configmaps:Secrets", "rw=deployments"]).unwrap();
+
+        assert!(options.read_write);
+        assert_eq!(
+            options.writable_kinds,
+            vec!["configmaps", "secrets", "deployments"]
+        );
+        assert!(options.unknown.is_empty());
+    }
+}
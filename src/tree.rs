@@ -1,10 +1,16 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
     num::NonZeroU64,
     sync::atomic::AtomicU64,
 };
 
+use tracing::warn;
+
+/// Deepest a walk goes. The mount is only a handful of levels deep, anything beyond this is a
+/// bug that would otherwise walk forever.
+const MAX_DEPTH: usize = 256;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct NodeId(NonZeroU64);
 
@@ -45,10 +51,17 @@ impl<T> Arena<T>
 where
     T: Debug + Clone + Send + Sync,
 {
+    // The vfs picks its base through `with_base`, this is left for the tests and benches.
+    #[allow(dead_code)]
     pub fn new() -> Self {
+        Arena::with_base(1)
+    }
+
+    /// Creates an arena that hands out ids starting at `base`, which can't be 0.
+    pub fn with_base(base: u64) -> Self {
         Arena {
             map: HashMap::new(),
-            counter: AtomicU64::new(1),
+            counter: AtomicU64::new(base),
         }
     }
 
@@ -90,6 +103,15 @@ where
         node
     }
 
+    pub fn get_mut(&mut self, node_id: &NodeId) -> Option<&mut Node<T>> {
+        self.map.get_mut(node_id)
+    }
+
+    /// Every node in the arena, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Node<T>> {
+        self.map.values()
+    }
+
     pub fn get_children(&self, parent: &NodeId) -> Option<Vec<&Node<T>>> {
         if !self.contains(&parent) {
             return None;
@@ -108,6 +130,40 @@ where
         None
     }
 
+    /// Walks the `parent_id` links upwards, returning the ids from `node_id` itself up to and
+    /// including the root of the tree.
+    pub fn path_to_root(&self, node_id: &NodeId) -> Option<Vec<NodeId>> {
+        let mut node = self.get(node_id)?;
+        let mut path = vec![node.id.clone()];
+        let mut visited = HashSet::from([node.id.clone()]);
+
+        while let Some(parent_id) = &node.parent_id {
+            // Same guards as `tree_walk_dfs`, a parent cycle would otherwise climb forever.
+            if !visited.insert(parent_id.clone()) {
+                warn!("Cycle in the tree at node {:?}, stopping there", parent_id);
+                break;
+            }
+
+            if path.len() > MAX_DEPTH {
+                warn!(
+                    "Tree deeper than {} at node {:?}, stopping there",
+                    MAX_DEPTH, node.id
+                );
+                break;
+            }
+
+            node = self.get(parent_id)?;
+            path.push(node.id.clone());
+        }
+
+        Some(path)
+    }
+
+    /// Number of ancestors between `node_id` and the root, the root itself has a depth of 0.
+    pub fn depth(&self, node_id: &NodeId) -> Option<usize> {
+        self.path_to_root(node_id).map(|path| path.len() - 1)
+    }
+
     fn generate_id(&self) -> NodeId {
         let id = self
             .counter
@@ -123,22 +179,38 @@ where
             return None;
         }
 
-        let mut stack = VecDeque::from([node_id.clone()]);
+        let mut stack = VecDeque::from([(node_id.clone(), 0)]);
+        let mut visited = HashSet::new();
 
         let mut it = VecDeque::new();
 
-        while let Some(node_id) = stack.pop_back() {
+        while let Some((node_id, depth)) = stack.pop_back() {
             // Question mark operator works below, since it returns a `Option` to `while let ...`.
             // Basically skip to the next item in the `stack` if `node_id` can't be found.
             let node = self.get(&node_id)?;
 
+            // A tree never reaches a node twice, so this is a cycle. Stop there and hand back
+            // what was walked so far rather than hanging.
+            if !visited.insert(node_id.clone()) {
+                warn!("Cycle in the tree at node {:?}, skipping it", node_id);
+                continue;
+            }
+
             it.push_back(node.id.clone());
 
+            if depth == MAX_DEPTH {
+                warn!(
+                    "Tree deeper than {} at node {:?}, skipping its children",
+                    MAX_DEPTH, node_id
+                );
+                continue;
+            }
+
             // Note that the children ordering has to be flipped! You want to perform the
             // traversal from RIGHT -> LEFT (not LEFT -> RIGHT).
             // PTAL: <https://developerlife.com/assets/algo-ts-2-images/depth-first-search.svg>
             for child_id in node.children_ids.iter().rev() {
-                stack.push_back(child_id.clone());
+                stack.push_back((child_id.clone(), depth + 1));
             }
         }
 
@@ -177,4 +249,232 @@ where
         // Pass the deletion list back.
         deletion_list.into()
     }
+
+    /// Detaches `node_id` (and with it its whole subtree) from its current parent and appends it
+    /// to the children of `new_parent`. Moving a node underneath itself or one of its own
+    /// descendants would create a cycle and is rejected.
+    // Nothing in the vfs reparents nodes yet, renames are meant to build on this.
+    #[allow(dead_code)]
+    pub fn move_node(&mut self, node_id: &NodeId, new_parent: &NodeId) -> anyhow::Result<()> {
+        if !self.contains(new_parent) {
+            return Err(anyhow::Error::msg("New parent node not found!"));
+        }
+
+        let subtree = self
+            .tree_walk_dfs(node_id)
+            .ok_or(anyhow::Error::msg("Node not found!"))?;
+
+        if subtree.contains(new_parent) {
+            return Err(anyhow::Error::msg(
+                "Can't move a node underneath one of its own descendants!",
+            ));
+        }
+
+        let node = self.map.get_mut(node_id).expect("node exists");
+        let old_parent = node.parent_id.replace(new_parent.clone());
+
+        if let Some(old_parent) = old_parent {
+            if let Some(parent_node) = self.map.get_mut(&old_parent) {
+                parent_node
+                    .children_ids
+                    .retain(|child_id| child_id != node_id);
+            }
+        }
+
+        if let Some(parent_node) = self.map.get_mut(new_parent) {
+            parent_node.children_ids.push_back(node_id.clone());
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Arena<T>
+where
+    T: Debug + Clone + Send + Sync + PartialEq,
+{
+    /// Brings the children of `parent` in line with `leafs`: payloads that are new get added and
+    /// children that are no longer in `leafs` get deleted, unless `keep` says otherwise. Children
+    /// present in both are left alone so their ids stay stable. Returns the ids of every node that
+    /// got deleted, the descendants of removed children included.
+    pub fn sync_children(
+        &mut self,
+        parent: &NodeId,
+        leafs: Vec<T>,
+        keep: impl Fn(&T) -> bool,
+    ) -> Option<Vec<NodeId>> {
+        let old_leafs: Vec<(NodeId, T)> = self
+            .get_children(parent)?
+            .iter()
+            .map(|n| (n.id.clone(), n.payload.clone()))
+            .collect();
+
+        let remove_ids: Vec<NodeId> = old_leafs
+            .iter()
+            .filter(|(_, n)| !leafs.contains(n) && !keep(n))
+            .map(|(i, _)| i.clone())
+            .collect();
+
+        let mut removed = Vec::new();
+        for id in remove_ids {
+            removed.extend(self.delete_node(id).unwrap_or_default());
+        }
+
+        for leaf in leafs {
+            if !old_leafs.iter().any(|(_, o)| *o == leaf) {
+                self.add(leaf, Some(parent.clone()));
+            }
+        }
+
+        Some(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_depth_first() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+        let a1 = arena.add("a1", Some(a.clone()));
+        let b = arena.add("b", Some(root.clone()));
+
+        let walk = arena.tree_walk_dfs(&root).unwrap();
+
+        assert_eq!(walk, VecDeque::from([root, a, a1, b]));
+    }
+
+    #[test]
+    fn walks_up_to_the_root() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+        let a1 = arena.add("a1", Some(a.clone()));
+        arena.add("b", Some(root.clone()));
+
+        assert_eq!(
+            arena.path_to_root(&a1),
+            Some(vec![a1.clone(), a, root.clone()])
+        );
+        assert_eq!(arena.path_to_root(&root), Some(vec![root.clone()]));
+        assert_eq!(arena.path_to_root(&NodeId::new(42)), None);
+        assert_eq!(arena.depth(&a1), Some(2));
+        assert_eq!(arena.depth(&root), Some(0));
+        assert_eq!(arena.depth(&NodeId::new(42)), None);
+    }
+
+    #[test]
+    fn stops_walking_up_at_a_cycle() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+        arena.get_mut(&root).unwrap().parent_id = Some(a.clone());
+
+        assert_eq!(arena.path_to_root(&a), Some(vec![a, root]));
+    }
+
+    #[test]
+    fn stops_walking_up_at_the_max_depth() {
+        let mut arena = Arena::new();
+        let mut node = arena.add("root", None);
+        for _ in 0..MAX_DEPTH + 10 {
+            node = arena.add("child", Some(node));
+        }
+
+        assert_eq!(arena.path_to_root(&node).unwrap().len(), MAX_DEPTH + 1);
+    }
+
+    #[test]
+    fn moves_a_subtree_to_another_parent() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+        let a1 = arena.add("a1", Some(a.clone()));
+        let b = arena.add("b", Some(root.clone()));
+
+        arena.move_node(&a, &b).unwrap();
+
+        assert_eq!(arena.get(&a).unwrap().parent_id, Some(b.clone()));
+        assert_eq!(
+            arena.get(&root).unwrap().children_ids,
+            VecDeque::from([b.clone()])
+        );
+        assert_eq!(
+            arena.get(&b).unwrap().children_ids,
+            VecDeque::from([a.clone()])
+        );
+        assert_eq!(
+            arena.tree_walk_dfs(&root).unwrap(),
+            VecDeque::from([root, b, a, a1])
+        );
+    }
+
+    #[test]
+    fn refuses_to_move_a_node_under_its_descendant() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+        let a1 = arena.add("a1", Some(a.clone()));
+
+        assert!(arena.move_node(&a, &a1).is_err());
+        assert!(arena.move_node(&a, &a).is_err());
+        assert_eq!(arena.get(&a).unwrap().parent_id, Some(root.clone()));
+        assert_eq!(arena.get(&a).unwrap().children_ids, VecDeque::from([a1]));
+    }
+
+    #[test]
+    fn hands_out_ids_from_the_base() {
+        let mut arena = Arena::with_base(1_000_000);
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+
+        assert_eq!(root, NodeId::new(1_000_000));
+        assert_eq!(a, NodeId::new(1_000_001));
+    }
+
+    #[test]
+    fn stops_walking_at_a_cycle() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+        arena
+            .get_mut(&a)
+            .unwrap()
+            .children_ids
+            .push_back(root.clone());
+
+        let walk = arena.tree_walk_dfs(&root).unwrap();
+
+        assert_eq!(walk, VecDeque::from([root, a]));
+    }
+
+    #[test]
+    fn stops_walking_at_the_max_depth() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let mut parent = root.clone();
+        for _ in 0..MAX_DEPTH + 10 {
+            parent = arena.add("child", Some(parent));
+        }
+
+        assert_eq!(arena.tree_walk_dfs(&root).unwrap().len(), MAX_DEPTH + 1);
+    }
+
+    #[test]
+    fn reports_the_nodes_a_sync_removes() {
+        let mut arena = Arena::new();
+        let root = arena.add("root", None);
+        let a = arena.add("a", Some(root.clone()));
+        let a1 = arena.add("a1", Some(a.clone()));
+        arena.add("b", Some(root.clone()));
+
+        let removed = arena.sync_children(&root, vec!["b", "c"], |_| false);
+
+        assert_eq!(removed, Some(vec![a.clone(), a1.clone()]));
+        assert!(!arena.contains(&a) && !arena.contains(&a1));
+        assert_eq!(arena.get_children(&root).unwrap().len(), 2);
+    }
 }
@@ -1,20 +1,33 @@
-use std::{collections::HashMap, fmt::Debug, time::SystemTime};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    io::{self, Read},
+    time::{Duration, SystemTime},
+};
 
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::GzDecoder;
 use fuser::{FileAttr, FileType};
+use k8s_openapi::{
+    api::core::v1::Event as KubeEvent,
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceColumnDefinition,
+    chrono::{DateTime, Utc},
+};
 use kube::{
+    api::{LogParams, Patch},
     core::DynamicObject,
     discovery::{verbs, ApiCapabilities, ApiResource, Scope},
     ResourceExt,
 };
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, info, trace, warn};
 
-use crate::client::KubeClient;
+use crate::client::{ApiError, Connection, KubeClient, ResourceChange};
+use crate::options::{KubeFsOptions, KubeJsonStyle, KubeManifestType, Layout};
 use crate::tree::{Arena, Node, NodeId};
-
-#[derive(Debug, Clone)]
-pub enum KubeManifestType {
-    Json,
-    Yaml,
-}
+use crate::VERSION;
 
 #[derive(Debug, Clone)]
 pub struct KubeApiResourceDirectory {
@@ -34,7 +47,10 @@ impl KubeApiResourceDirectory {
 pub struct KubeManifestFile {
     pub name: String,
     pub file_type: KubeManifestType,
+    pub json_style: KubeJsonStyle,
     pub data: DynamicObject,
+    /// Length the manifest gets truncated at, `-o max_manifest_bytes`.
+    pub max_bytes: Option<usize>,
 }
 
 impl KubeManifestFile {
@@ -46,10 +62,24 @@ impl KubeManifestFile {
     pub fn to_string(&self) -> String {
         let mut obj = self.data.clone();
         obj.metadata.managed_fields = None;
-        match self.file_type {
+        let mut manifest = match self.file_type {
             KubeManifestType::Yaml => serde_yaml::to_string(&obj).unwrap_or_default(),
-            KubeManifestType::Json => serde_json::to_string(&obj).unwrap_or_default(),
+            KubeManifestType::Json => match self.json_style {
+                KubeJsonStyle::Pretty => serde_json::to_string_pretty(&obj).unwrap_or_default(),
+                KubeJsonStyle::Compact => serde_json::to_string(&obj).unwrap_or_default(),
+            },
+        };
+
+        if let Some(max_bytes) = self.max_bytes.filter(|max| manifest.len() > *max) {
+            let mut end = max_bytes;
+            while !manifest.is_char_boundary(end) {
+                end -= 1;
+            }
+            manifest.truncate(end);
+            manifest.push_str(TRUNCATED_MARKER);
         }
+
+        manifest
     }
 }
 
@@ -80,26 +110,43 @@ struct KubeResourceNode {
     uuid: String,
     name: String,
     kind: String,
+    /// Whether the file name includes the namespace, `<namespace>.<name>.<ext>`, for views
+    /// across namespaces like `~get` where names collide otherwise. Namespaces can't contain
+    /// dots, so the name is never ambiguous.
+    qualified: bool,
 }
 
 impl KubeResourceNode {
-    fn new(uuid: &str, name: &str, kind: &str) -> Self {
+    fn from(obj: &DynamicObject, kind: &str) -> Self {
         KubeResourceNode {
-            namespace: None,
-            uuid: uuid.into(),
-            name: name.into(),
+            namespace: obj.namespace(),
+            uuid: KubeResourceNode::identity(obj, kind),
+            name: obj.name_any(),
             kind: kind.into(),
+            qualified: false,
         }
     }
 
-    fn from(obj: &DynamicObject, kind: &str) -> Self {
+    /// A node named after its namespace as well, unless it has none because it's cluster scoped.
+    fn qualified(obj: &DynamicObject, kind: &str) -> Self {
         KubeResourceNode {
-            namespace: obj.namespace(),
-            uuid: obj.uid().unwrap(),
-            name: obj.name_any(),
-            kind: kind.into(),
+            qualified: obj.namespace().is_some(),
+            ..KubeResourceNode::from(obj, kind)
         }
     }
+
+    /// The uid of the object, objects coming from some aggregated apis or dry-run responses
+    /// don't have one so fall back to an id that is stable across listings.
+    fn identity(obj: &DynamicObject, kind: &str) -> String {
+        obj.uid().unwrap_or_else(|| {
+            format!(
+                "{}/{}/{}",
+                obj.namespace().unwrap_or_default(),
+                kind,
+                obj.name_any()
+            )
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -110,20 +157,211 @@ enum KubeFileNode {
     ApiResourceDirectory(KubeApiResourceNode),
     ResourceDirectory(KubeResourceNode),
     ResourceFile(KubeResourceNode),
-    LogFile(KubeResourceNode),
+    /// Logs of a pod, `logs.ts` has every line prefixed with its timestamp when the bool is set.
+    LogFile(KubeResourceNode, bool),
+    PodSummaryFile(KubeResourceNode),
+    /// `events`, the events about the resource of the directory it's in.
+    EventsFile(KubeResourceNode),
+    /// `containers`, holds a directory per container of a pod, init containers included.
+    ContainersDirectory(KubeResourceNode),
+    ContainerDirectory(KubeResourceNode, String),
+    /// Logs of a single container, `logs.previous` holds those of its previous instance.
+    ContainerLogFile(KubeResourceNode, String, bool),
+    ContainerImageFile(KubeResourceNode, String),
+    ContainerStatusFile(KubeResourceNode, String),
+    NodeMetricsFile,
+    PodMetricsFile(KubeResourceNode),
+    /// `quota` in a namespace, the usage of its resource quotas and the defaults of its limit
+    /// ranges. Left out of namespaces without either.
+    QuotaFile(KubeResourceNode),
+    /// `.apiservices`, every registered api service and whether it is available, similar to
+    /// `kubectl get apiservices`.
+    ApiServicesFile,
+    /// Recent failed requests to the api server, at the root of the mount.
+    ErrorsFile,
+    /// `.meta` in every directory, tells when it was last synced with the cluster and how much of
+    /// it is cached, for when the data in it looks stale.
+    MetaFile,
+    /// `.resources`, every kind the mount serves, similar to `kubectl api-resources`.
+    ResourcesFile,
+    /// `.version`, the versions of kubefs, the api server and the kube crate for bug reports.
+    VersionFile,
+    /// `.connection`, the api server the mount talks to and how it authenticates, for telling
+    /// which cluster and identity it uses. Never includes the credentials themselves.
+    ConnectionFile,
+    /// `.kubefs`, holds the files to control the mount itself.
+    ControlDirectory,
+    /// `.kubefs/flush`, writing anything to it drops every cached response. Like any write this
+    /// needs the mount to be writable, `-o rw`.
+    FlushFile,
+    /// `.kubefs/inodes`, maps every inode to its path, for debugging.
+    InodesFile,
+    /// `.kubefs/index.json`, describes the layout of the mount for tools driving it.
+    IndexFile,
+    /// `.apply`, manifests copied into it get applied to the cluster. Only there with `-o rw`.
+    ApplyDirectory,
+    /// A manifest dropped in `.apply`, applied once its contents are flushed.
+    ApplyFile(String),
+    /// `.apply/last-result`, the outcome of the last manifest that got applied.
+    ApplyResultFile,
+    /// `.patch` in the directory of a writable resource, a JSON patch (RFC 6902) written to it
+    /// gets applied to the resource once flushed.
+    PatchFile(KubeResourceNode),
+    /// `.patch-result`, the outcome of the last patch written to the `.patch` next to it.
+    PatchResultFile(KubeResourceNode),
+    /// `<name>.diff`, the drift of a resource from its last applied configuration.
+    DiffFile(KubeResourceNode),
+    /// `<name>.scale` next to a workload, its replicas and the autoscaler targeting it.
+    ScaleFile(KubeResourceNode),
+    /// A single key of the data held by a ConfigMap or Secret.
+    DataKeyFile(KubeResourceNode, String),
+    TableFile(KubeApiResourceNode),
+    /// `README` in a kind directory with `-o readme`, describes the kind and its resources.
+    ReadmeFile(KubeApiResourceNode),
+    /// `.explain` in a kind directory with `-o explain`, the documentation of its fields.
+    ExplainFile(KubeApiResourceNode),
+    /// `labels` in a kind directory, a directory per label key used by the resources of the kind.
+    LabelsDirectory(KubeApiResourceNode),
+    /// A label key in `labels`, holding a directory per value of the label.
+    LabelKeyDirectory(KubeApiResourceNode, String),
+    /// A value of a label, listing the manifests of the resources labeled with it, e.g.
+    /// `pods/labels/app/nginx`.
+    LabelValueDirectory(KubeApiResourceNode, String, String),
+    /// `ready` and `not-ready` in a kind directory with `-o health_split`, the pods or workloads
+    /// of the kind that are ready when the bool is set and those that aren't otherwise.
+    HealthDirectory(KubeApiResourceNode, bool),
+    /// An api group in a namespace with `-o group_by_apigroup`, holding the kinds of the group.
+    /// Kinds of the core group stay in the namespace itself.
+    ApiGroupDirectory(String, String),
+    /// A namespace underneath a kind, only used by the kind first layout.
+    NamespaceDirectory(KubeApiResourceNode),
+    /// `~recent`, lists the resources that recently changed according to the watches.
+    RecentDirectory,
+    RecentLink(ResourceChange),
+    /// `current` in the directory of a resource, a link to its manifest next to the directory.
+    /// Gives scripts a path that doesn't depend on the format or extension of the manifests.
+    CurrentLink(KubeResourceNode),
+    /// `owner` in the directory of a resource owned by another one, a link to the manifest of its
+    /// controller, e.g. the ReplicaSet of a pod.
+    OwnerLink(KubeResourceNode),
+    /// `~get`, a directory per kind listing it across every namespace like `kubectl get -A`.
+    GetDirectory,
+    GetKindDirectory(KubeApiResourceNode),
+    /// `.confirm` in a namespace directory, created to confirm the namespace may be deleted with
+    /// `-o allow_recursive_delete`. It only exists locally.
+    ConfirmDeleteFile(String),
+    /// A manifest created in a kind directory with `-o rw`, which becomes a resource once
+    /// something is written to it.
+    NewResourceFile(KubeApiResourceNode, String),
+    /// A manifest as of a resourceVersion, looked up as `<name>@<version>.<ext>`. Hangs off the
+    /// manifest of the current version.
+    VersionedResourceFile(KubeResourceNode, String),
+    /// A single field of a manifest, looked up as `<manifest>?<path>`, e.g. `foo.yml?.status.phase`.
+    /// Hangs off the manifest it projects so it never shows up in a directory listing.
+    ProjectionFile(KubeResourceNode, String),
+    /// The fields of a manifest owned by a field manager according to its managedFields, looked
+    /// up as `<name>.owned.<manager>.<ext>`. Hangs off the manifest like the projections.
+    OwnedFieldsFile(KubeResourceNode, String),
 }
 
 impl KubeFileNode {
-    pub fn get_file_name(&self) -> String {
+    /// Name of the node in its parent directory, `extension` is used for the manifest files.
+    pub fn get_file_name(&self, extension: &str) -> String {
         match self {
             KubeFileNode::Context(name) | KubeFileNode::Virtual(name) => name.clone(),
             KubeFileNode::ClusterInfoFile => "cluster_info".into(),
             KubeFileNode::ApiResourceDirectory(api) => api.name(),
             KubeFileNode::ResourceDirectory(r) => r.name.clone(),
-            KubeFileNode::ResourceFile(r) => format!("{}.yml", r.name),
-            KubeFileNode::LogFile(_) => "logs".into(),
+            KubeFileNode::ResourceFile(r) => match &r.namespace {
+                Some(namespace) if r.qualified => {
+                    format!("{}.{}.{}", namespace, r.name, extension)
+                }
+                _ => format!("{}.{}", r.name, extension),
+            },
+            KubeFileNode::ConfirmDeleteFile(_) => CONFIRM_DELETE_FILE.into(),
+            KubeFileNode::NewResourceFile(_, name) => name.clone(),
+            KubeFileNode::VersionedResourceFile(r, version) => {
+                format!("{}@{}.{}", r.name, version, extension)
+            }
+            KubeFileNode::ProjectionFile(r, path) => format!("{}.{}?{}", r.name, extension, path),
+            KubeFileNode::OwnedFieldsFile(r, manager) => {
+                format!("{}.owned.{}.{}", r.name, manager, extension)
+            }
+            KubeFileNode::LogFile(_, false) => "logs".into(),
+            KubeFileNode::LogFile(_, true) => "logs.ts".into(),
+            KubeFileNode::PodSummaryFile(_) => "summary".into(),
+            KubeFileNode::EventsFile(_) => "events".into(),
+            KubeFileNode::QuotaFile(_) => "quota".into(),
+            KubeFileNode::ContainersDirectory(_) => "containers".into(),
+            KubeFileNode::ContainerDirectory(_, container) => container.clone(),
+            KubeFileNode::ContainerLogFile(_, _, false) => "logs".into(),
+            KubeFileNode::ContainerLogFile(_, _, true) => "logs.previous".into(),
+            KubeFileNode::ContainerImageFile(..) => "image".into(),
+            KubeFileNode::ContainerStatusFile(..) => "status".into(),
+            KubeFileNode::DiffFile(r) => format!("{}.diff", r.name),
+            KubeFileNode::ScaleFile(r) => format!("{}.scale", r.name),
+            KubeFileNode::NodeMetricsFile => ".metrics".into(),
+            KubeFileNode::ApiServicesFile => ".apiservices".into(),
+            KubeFileNode::PodMetricsFile(_) => "metrics".into(),
+            KubeFileNode::ErrorsFile => ".errors".into(),
+            KubeFileNode::MetaFile => ".meta".into(),
+            KubeFileNode::ResourcesFile => ".resources".into(),
+            KubeFileNode::VersionFile => ".version".into(),
+            KubeFileNode::ConnectionFile => ".connection".into(),
+            KubeFileNode::ControlDirectory => ".kubefs".into(),
+            KubeFileNode::FlushFile => "flush".into(),
+            KubeFileNode::InodesFile => "inodes".into(),
+            KubeFileNode::IndexFile => "index.json".into(),
+            KubeFileNode::ApplyDirectory => ".apply".into(),
+            KubeFileNode::ApplyFile(name) => name.clone(),
+            KubeFileNode::ApplyResultFile => "last-result".into(),
+            KubeFileNode::PatchFile(_) => ".patch".into(),
+            KubeFileNode::PatchResultFile(_) => ".patch-result".into(),
+            KubeFileNode::DataKeyFile(_, key) => key.clone(),
+            KubeFileNode::TableFile(_) => ".table".into(),
+            KubeFileNode::ReadmeFile(_) => "README".into(),
+            KubeFileNode::ExplainFile(_) => ".explain".into(),
+            KubeFileNode::LabelsDirectory(_) => LABELS_DIRECTORY.into(),
+            // Label keys can be prefixed, `app.kubernetes.io/name`, but a slash can't be part of a
+            // file name. Colons aren't allowed in label keys, so they take its place.
+            KubeFileNode::LabelKeyDirectory(_, key) => key.replace('/', ":"),
+            KubeFileNode::LabelValueDirectory(_, _, value) => value.clone(),
+            KubeFileNode::HealthDirectory(_, true) => "ready".into(),
+            KubeFileNode::HealthDirectory(_, false) => "not-ready".into(),
+            KubeFileNode::NamespaceDirectory(api) => api.namespace.clone().unwrap_or_default(),
+            KubeFileNode::RecentDirectory => "~recent".into(),
+            KubeFileNode::RecentLink(c) => format!("{}.{}.{}", c.namespace, c.plural, c.name),
+            KubeFileNode::CurrentLink(_) => CURRENT_LINK.into(),
+            KubeFileNode::OwnerLink(_) => OWNER_LINK.into(),
+            KubeFileNode::ApiGroupDirectory(_, group) => group.clone(),
+            KubeFileNode::GetDirectory => "~get".into(),
+            KubeFileNode::GetKindDirectory(api) => api.name(),
         }
     }
+
+    /// Whether the node shows up as a directory, without having to stat it.
+    pub fn is_directory(&self) -> bool {
+        matches!(
+            self,
+            KubeFileNode::Virtual(_)
+                | KubeFileNode::Context(_)
+                | KubeFileNode::ApiResourceDirectory(_)
+                | KubeFileNode::NamespaceDirectory(_)
+                | KubeFileNode::RecentDirectory
+                | KubeFileNode::ApiGroupDirectory(..)
+                | KubeFileNode::GetDirectory
+                | KubeFileNode::GetKindDirectory(_)
+                | KubeFileNode::LabelsDirectory(_)
+                | KubeFileNode::LabelKeyDirectory(..)
+                | KubeFileNode::LabelValueDirectory(..)
+                | KubeFileNode::HealthDirectory(..)
+                | KubeFileNode::ControlDirectory
+                | KubeFileNode::ApplyDirectory
+                | KubeFileNode::ContainersDirectory(_)
+                | KubeFileNode::ContainerDirectory(..)
+                | KubeFileNode::ResourceDirectory(_)
+        )
+    }
 }
 
 impl PartialEq<KubeFileNode> for KubeFileNode {
@@ -142,6 +380,70 @@ impl PartialEq<KubeFileNode> for KubeFileNode {
                 KubeFileNode::ClusterInfoFile => true,
                 _ => false,
             },
+            KubeFileNode::ErrorsFile => match other {
+                KubeFileNode::ErrorsFile => true,
+                _ => false,
+            },
+            KubeFileNode::MetaFile => match other {
+                KubeFileNode::MetaFile => true,
+                _ => false,
+            },
+            KubeFileNode::ResourcesFile => match other {
+                KubeFileNode::ResourcesFile => true,
+                _ => false,
+            },
+            KubeFileNode::ApiServicesFile => match other {
+                KubeFileNode::ApiServicesFile => true,
+                _ => false,
+            },
+            KubeFileNode::VersionFile => match other {
+                KubeFileNode::VersionFile => true,
+                _ => false,
+            },
+            KubeFileNode::ConnectionFile => match other {
+                KubeFileNode::ConnectionFile => true,
+                _ => false,
+            },
+            KubeFileNode::ControlDirectory => match other {
+                KubeFileNode::ControlDirectory => true,
+                _ => false,
+            },
+            KubeFileNode::FlushFile => match other {
+                KubeFileNode::FlushFile => true,
+                _ => false,
+            },
+            KubeFileNode::InodesFile => match other {
+                KubeFileNode::InodesFile => true,
+                _ => false,
+            },
+            KubeFileNode::IndexFile => match other {
+                KubeFileNode::IndexFile => true,
+                _ => false,
+            },
+            KubeFileNode::ApplyDirectory => match other {
+                KubeFileNode::ApplyDirectory => true,
+                _ => false,
+            },
+            KubeFileNode::ApplyFile(l) => match other {
+                KubeFileNode::ApplyFile(r) => l == r,
+                _ => false,
+            },
+            KubeFileNode::ConfirmDeleteFile(l) => match other {
+                KubeFileNode::ConfirmDeleteFile(r) => l == r,
+                _ => false,
+            },
+            KubeFileNode::ApplyResultFile => match other {
+                KubeFileNode::ApplyResultFile => true,
+                _ => false,
+            },
+            KubeFileNode::PatchFile(l) => match other {
+                KubeFileNode::PatchFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::PatchResultFile(l) => match other {
+                KubeFileNode::PatchResultFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
             KubeFileNode::ApiResourceDirectory(l) => match other {
                 KubeFileNode::ApiResourceDirectory(r) => {
                     l.kind == r.kind && l.group == r.group && l.version == r.version
@@ -152,12 +454,163 @@ impl PartialEq<KubeFileNode> for KubeFileNode {
                 KubeFileNode::ResourceDirectory(r) => l.uuid == r.uuid,
                 _ => false,
             },
+            KubeFileNode::VersionedResourceFile(l, lv) => match other {
+                KubeFileNode::VersionedResourceFile(r, rv) => l.uuid == r.uuid && lv == rv,
+                _ => false,
+            },
+            KubeFileNode::ProjectionFile(l, lp) => match other {
+                KubeFileNode::ProjectionFile(r, rp) => l.uuid == r.uuid && lp == rp,
+                _ => false,
+            },
+            KubeFileNode::OwnedFieldsFile(l, lm) => match other {
+                KubeFileNode::OwnedFieldsFile(r, rm) => l.uuid == r.uuid && lm == rm,
+                _ => false,
+            },
             KubeFileNode::ResourceFile(l) => match other {
                 KubeFileNode::ResourceFile(r) => l.uuid == r.uuid,
                 _ => false,
             },
-            KubeFileNode::LogFile(l) => match other {
-                KubeFileNode::LogFile(r) => l.uuid == r.uuid,
+            KubeFileNode::LogFile(l, lts) => match other {
+                KubeFileNode::LogFile(r, rts) => l.uuid == r.uuid && lts == rts,
+                _ => false,
+            },
+            KubeFileNode::DiffFile(l) => match other {
+                KubeFileNode::DiffFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::ScaleFile(l) => match other {
+                KubeFileNode::ScaleFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::ContainersDirectory(l) => match other {
+                KubeFileNode::ContainersDirectory(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::ContainerDirectory(l, lc) => match other {
+                KubeFileNode::ContainerDirectory(r, rc) => l.uuid == r.uuid && lc == rc,
+                _ => false,
+            },
+            KubeFileNode::ContainerLogFile(l, lc, lp) => match other {
+                KubeFileNode::ContainerLogFile(r, rc, rp) => {
+                    l.uuid == r.uuid && lc == rc && lp == rp
+                }
+                _ => false,
+            },
+            KubeFileNode::ContainerImageFile(l, lc) => match other {
+                KubeFileNode::ContainerImageFile(r, rc) => l.uuid == r.uuid && lc == rc,
+                _ => false,
+            },
+            KubeFileNode::ContainerStatusFile(l, lc) => match other {
+                KubeFileNode::ContainerStatusFile(r, rc) => l.uuid == r.uuid && lc == rc,
+                _ => false,
+            },
+            KubeFileNode::PodSummaryFile(l) => match other {
+                KubeFileNode::PodSummaryFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::EventsFile(l) => match other {
+                KubeFileNode::EventsFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::QuotaFile(l) => match other {
+                KubeFileNode::QuotaFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::NodeMetricsFile => match other {
+                KubeFileNode::NodeMetricsFile => true,
+                _ => false,
+            },
+            KubeFileNode::PodMetricsFile(l) => match other {
+                KubeFileNode::PodMetricsFile(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::NewResourceFile(l, lname) => match other {
+                KubeFileNode::NewResourceFile(r, rname) => {
+                    l.namespace == r.namespace && l.kind == r.kind && lname == rname
+                }
+                _ => false,
+            },
+            KubeFileNode::DataKeyFile(l, lk) => match other {
+                KubeFileNode::DataKeyFile(r, rk) => l.uuid == r.uuid && lk == rk,
+                _ => false,
+            },
+            KubeFileNode::ReadmeFile(l) => match other {
+                KubeFileNode::ReadmeFile(r) => {
+                    l.kind == r.kind && l.group == r.group && l.version == r.version
+                }
+                _ => false,
+            },
+            KubeFileNode::ExplainFile(l) => match other {
+                KubeFileNode::ExplainFile(r) => {
+                    l.kind == r.kind && l.group == r.group && l.version == r.version
+                }
+                _ => false,
+            },
+            KubeFileNode::TableFile(l) => match other {
+                KubeFileNode::TableFile(r) => {
+                    l.kind == r.kind && l.group == r.group && l.version == r.version
+                }
+                _ => false,
+            },
+            KubeFileNode::NamespaceDirectory(l) => match other {
+                KubeFileNode::NamespaceDirectory(r) => l.namespace == r.namespace,
+                _ => false,
+            },
+            KubeFileNode::LabelsDirectory(l) => match other {
+                KubeFileNode::LabelsDirectory(r) => l.namespace == r.namespace && l.kind == r.kind,
+                _ => false,
+            },
+            KubeFileNode::LabelKeyDirectory(l, lk) => match other {
+                KubeFileNode::LabelKeyDirectory(r, rk) => {
+                    l.namespace == r.namespace && l.kind == r.kind && lk == rk
+                }
+                _ => false,
+            },
+            KubeFileNode::HealthDirectory(l, lr) => match other {
+                KubeFileNode::HealthDirectory(r, rr) => {
+                    l.namespace == r.namespace && l.kind == r.kind && lr == rr
+                }
+                _ => false,
+            },
+            KubeFileNode::LabelValueDirectory(l, lk, lv) => match other {
+                KubeFileNode::LabelValueDirectory(r, rk, rv) => {
+                    l.namespace == r.namespace && l.kind == r.kind && lk == rk && lv == rv
+                }
+                _ => false,
+            },
+            KubeFileNode::RecentDirectory => match other {
+                KubeFileNode::RecentDirectory => true,
+                _ => false,
+            },
+            KubeFileNode::CurrentLink(l) => match other {
+                KubeFileNode::CurrentLink(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::OwnerLink(l) => match other {
+                KubeFileNode::OwnerLink(r) => l.uuid == r.uuid,
+                _ => false,
+            },
+            KubeFileNode::RecentLink(l) => match other {
+                KubeFileNode::RecentLink(r) => {
+                    l.namespace == r.namespace
+                        && l.plural == r.plural
+                        && l.name == r.name
+                        && l.time == r.time
+                }
+                _ => false,
+            },
+            KubeFileNode::ApiGroupDirectory(ln, lg) => match other {
+                KubeFileNode::ApiGroupDirectory(rn, rg) => ln == rn && lg == rg,
+                _ => false,
+            },
+            KubeFileNode::GetDirectory => match other {
+                KubeFileNode::GetDirectory => true,
+                _ => false,
+            },
+            KubeFileNode::GetKindDirectory(l) => match other {
+                KubeFileNode::GetKindDirectory(r) => {
+                    l.kind == r.kind && l.group == r.group && l.version == r.version
+                }
                 _ => false,
             },
         }
@@ -166,54 +619,260 @@ impl PartialEq<KubeFileNode> for KubeFileNode {
 
 pub struct KubeVirtualFs {
     kube_client: KubeClient,
-    aliases: HashMap<String, String>,
     api_resources: Vec<(ApiResource, ApiCapabilities)>,
     arena_two: Arena<KubeFileNode>,
+    /// Id of the root of the tree, the first inode handed out.
+    root: NodeId,
+    layout: Layout,
+    watch: bool,
+    decompress: bool,
+    manifest_type: KubeManifestType,
+    json_style: KubeJsonStyle,
+    extension: String,
+    blksize: u32,
+    max_manifest_bytes: Option<usize>,
+    metrics_available: bool,
+    lazy: bool,
+    hide_empty: bool,
+    hide_system_namespaces: bool,
+    hidden_namespaces: Vec<String>,
+    api_groups: Vec<String>,
+    verbs: Vec<String>,
+    group_by_apigroup: bool,
+    health_split: bool,
+    readme: bool,
+    explain: bool,
+    hide_manifest: bool,
+    wide: bool,
+    strict: bool,
+    log_timestamps: bool,
+    log_since: Option<Duration>,
+    read_write: bool,
+    writable_kinds: Vec<String>,
+    allow_recursive_delete: bool,
+    refresh_on_open: bool,
+    apply_result: String,
+    /// Outcome of the last patch written to the `.patch` file of a resource, by uid. Newest
+    /// first, up to `RECENT_PATCH_RESULTS` of them.
+    patch_results: VecDeque<(String, String)>,
+    write_buffers: HashMap<u64, Vec<u8>>,
+    /// How the last syncs of directories went, by inode.
+    sync_states: HashMap<u64, SyncState>,
     startup: SystemTime,
 }
 
 impl KubeVirtualFs {
-    pub fn new(kube_client: KubeClient) -> Self {
-        let mut arena_two = Arena::new();
-        arena_two.add(KubeFileNode::Context("default".into()), None);
+    pub fn new(kube_client: KubeClient, options: &KubeFsOptions) -> anyhow::Result<Self> {
+        let aliases = &options.aliases;
 
-        let aliases = HashMap::from([
-            ("service".into(), "svc".into()),
-            ("deployment".into(), "deploy".into()),
-        ]);
+        let (api_resources, metrics_available) =
+            KubeVirtualFs::discover(&kube_client, &options.api_groups, &options.verbs)?;
 
-        let filter = vec![
-            "",
-            "apps",
-            "batch",
-            "networking.k8s.io",
-            "rbac.authorization.k8s.iol",
-        ];
-        let ops = vec![verbs::LIST];
+        kube_client.watch_namespaces();
 
-        let api_resources = kube_client
-            .discover_api_resources(Some(&filter), Some(&ops))
-            .unwrap();
+        let root = match &options.root {
+            Some(path) => KubeVirtualFs::resolve_root(&kube_client, &api_resources, aliases, path)?,
+            None if options.current_namespace => KubeVirtualFs::resolve_root(
+                &kube_client,
+                &api_resources,
+                aliases,
+                &kube_client.default_namespace(),
+            )?,
+            None => KubeVirtualFs::context_node(&kube_client),
+        };
+
+        let mut arena_two = Arena::with_base(options.inode_base);
+        let root = arena_two.add(root, None);
 
-        KubeVirtualFs {
+        Ok(KubeVirtualFs {
             kube_client,
-            aliases,
             arena_two,
+            root,
             api_resources,
+            layout: options.layout,
+            watch: options.watch,
+            decompress: options.decompress,
+            manifest_type: options.format.clone(),
+            json_style: options.json_style.clone(),
+            extension: options.extension.clone(),
+            blksize: options.blksize,
+            max_manifest_bytes: options.max_manifest_bytes,
+            metrics_available,
+            lazy: options.lazy,
+            hide_empty: options.hide_empty,
+            hide_system_namespaces: options.hide_system_namespaces,
+            hidden_namespaces: options.hidden_namespaces.clone(),
+            api_groups: options.api_groups.clone(),
+            verbs: options.verbs.clone(),
+            group_by_apigroup: options.group_by_apigroup,
+            health_split: options.health_split,
+            readme: options.readme,
+            explain: options.explain,
+            hide_manifest: options.hide_manifest,
+            wide: options.wide,
+            strict: options.strict,
+            log_timestamps: options.log_timestamps,
+            log_since: options.log_since,
+            read_write: options.read_write,
+            writable_kinds: options.writable_kinds.clone(),
+            allow_recursive_delete: options.allow_recursive_delete,
+            refresh_on_open: options.refresh_on_open,
+            apply_result: String::new(),
+            patch_results: VecDeque::with_capacity(RECENT_PATCH_RESULTS),
+            write_buffers: HashMap::new(),
+            sync_states: HashMap::new(),
             startup: SystemTime::now(),
-        }
+        })
+    }
+
+    /// Inode of the root of the mount, `inode_base` when set.
+    pub fn root_inode(&self) -> u64 {
+        self.root.clone().into()
+    }
+
+    /// Discovers the kinds the mount serves, and whether metrics are available.
+    fn discover(
+        kube_client: &KubeClient,
+        api_groups: &[String],
+        verbs: &[String],
+    ) -> anyhow::Result<(Vec<(ApiResource, ApiCapabilities)>, bool)> {
+        let filter: Vec<&str> = api_groups.iter().map(String::as_str).collect();
+        let ops = vec![verbs::LIST];
+        let mut required = ops.clone();
+        required.extend(verbs.iter().map(String::as_str));
+
+        let api_resources = kube_client
+            .discover_api_resources(Some(&filter), Some(&required))
+            .context("Unable to discover the api resources served by the cluster")?;
+
+        // Metrics are only served when metrics-server (or an alternative) is installed.
+        let metrics_available = kube_client
+            .discover_api_resources(Some(&vec!["metrics.k8s.io"]), Some(&ops))
+            .map(|resources| !resources.is_empty())
+            .unwrap_or(false);
+
+        Ok((api_resources, metrics_available))
+    }
+
+    /// Block size of every file in the mount, `-o blksize`.
+    pub fn block_size(&self) -> u32 {
+        self.blksize
+    }
+
+    /// Sets the process the requests made from here on are for, so they get cancelled when it
+    /// gives up on them.
+    pub fn set_caller(&self, pid: Option<u32>) {
+        self.kube_client.set_caller(pid);
+    }
+
+    /// Reconnects, drops every cached response and runs discovery again, so kinds that got
+    /// installed since the mount show up.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        self.kube_client.reload()?;
+
+        let (api_resources, metrics_available) =
+            KubeVirtualFs::discover(&self.kube_client, &self.api_groups, &self.verbs)?;
+        self.api_resources = api_resources;
+        self.metrics_available = metrics_available;
+
+        Ok(())
+    }
+
+    /// Root of the tree, named after the current context of the kubeconfig.
+    fn context_node(kube_client: &KubeClient) -> KubeFileNode {
+        KubeFileNode::Context(
+            kube_client
+                .current_context()
+                .unwrap_or_else(|| String::from("default")),
+        )
+    }
+
+    /// Resolves a `[context/]namespace[/kind]` path to the node the tree should be rooted at.
+    fn resolve_root(
+        kube_client: &KubeClient,
+        api_resources: &[(ApiResource, ApiCapabilities)],
+        aliases: &HashMap<String, String>,
+        path: &str,
+    ) -> anyhow::Result<KubeFileNode> {
+        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+
+        let (namespace, kind) = match parts.as_slice() {
+            [] => return Ok(KubeVirtualFs::context_node(kube_client)),
+            [namespace] => (*namespace, None),
+            [namespace, kind] => (*namespace, Some(*kind)),
+            [context, namespace, kind] => {
+                if kube_client.current_context().as_deref() != Some(*context) {
+                    return Err(anyhow::Error::msg(format!(
+                        "Can't mount {}, only the current context can be mounted",
+                        path
+                    )));
+                }
+                (*namespace, Some(*kind))
+            }
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "Can't mount {}, expected a path like [context/]namespace[/kind]",
+                    path
+                )))
+            }
+        };
+
+        let namespace = kube_client
+            .list_namespaces()?
+            .into_iter()
+            .find(|n| n.name_any() == namespace)
+            .ok_or(anyhow::Error::msg(format!(
+                "Namespace {} not found",
+                namespace
+            )))?;
+
+        let kind = match kind {
+            Some(kind) => kind,
+            None => {
+                let n = KubeResourceNode::from(&namespace, "Namespace");
+                return Ok(KubeFileNode::ResourceDirectory(n));
+            }
+        };
+
+        let (api, _) = api_resources
+            .iter()
+            .filter(|(_, c)| c.scope == Scope::Namespaced)
+            .find(|(a, _)| {
+                let name = a.kind.to_lowercase();
+                a.plural == kind
+                    || name == kind
+                    || aliases.get(&name).map(|a| a.as_str()) == Some(kind)
+            })
+            .ok_or(anyhow::Error::msg(format!(
+                "Unknown namespaced resource {}",
+                kind
+            )))?;
+
+        Ok(KubeFileNode::ApiResourceDirectory(KubeApiResourceNode {
+            namespace: Some(namespace.name_any()),
+            group: api.group.clone(),
+            kind: api.kind.clone(),
+            version: api.version.clone(),
+            plural: api.plural.clone(),
+        }))
     }
 
     pub fn get_file_from_parent_by_name_two(
         &mut self,
         parent: u64,
         name: &str,
-    ) -> Option<(String, FileAttr)> {
-        self.sync_leafs_for_inode(parent);
+    ) -> anyhow::Result<Option<(String, FileAttr)>> {
+        if let Some(file) = self.lookup_lazy(parent, name) {
+            return Ok(file);
+        }
+
+        if !self.sync_leafs_for_inode(parent)? {
+            return Ok(None);
+        }
 
         let id = NodeId::new(parent);
 
-        println!(
+        trace!(
             "Found {} for {} and it is {:?}",
             name,
             parent,
@@ -222,42 +881,114 @@ impl KubeVirtualFs {
                 .map(|nodes| {
                     nodes
                         .iter()
-                        .map(|n| (n.payload.get_file_name(), self.map_kube_file_to_attr(n)))
+                        .map(|n| {
+                            (
+                                self.get_file_name(&n.payload),
+                                self.map_kube_file_to_attr(n),
+                            )
+                        })
                         .find(|f| f.0 == name)
                 })
                 .flatten()
         );
 
-        self.arena_two
+        Ok(self
+            .arena_two
             .get_children(&id)
             .map(|nodes| {
                 nodes
                     .iter()
-                    .map(|n| (n.payload.get_file_name(), self.map_kube_file_to_attr(n)))
+                    .map(|n| {
+                        (
+                            self.get_file_name(&n.payload),
+                            self.map_kube_file_to_attr(n),
+                        )
+                    })
                     .find(|f| f.0 == name)
             })
-            .flatten()
+            .flatten())
+    }
+
+    /// With `-o lazy` looking up a resource in a kind directory fetches just that resource
+    /// rather than listing the whole kind. Returns None when the lookup can't be served lazily.
+    fn lookup_lazy(&mut self, parent: u64, name: &str) -> Option<Option<(String, FileAttr)>> {
+        let id = NodeId::new(parent);
+        let api = match self.arena_two.get(&id).map(|n| &n.payload) {
+            Some(KubeFileNode::ApiResourceDirectory(api))
+            | Some(KubeFileNode::NamespaceDirectory(api))
+                if self.lazy && api.namespace.is_some() =>
+            {
+                api.clone()
+            }
+            _ => return None,
+        };
+
+        if let Some(existing) = self.arena_two.get_children(&id).and_then(|nodes| {
+            nodes
+                .iter()
+                .find(|n| self.get_file_name(&n.payload) == name)
+                .map(|n| {
+                    (
+                        self.get_file_name(&n.payload),
+                        self.map_kube_file_to_attr(n),
+                    )
+                })
+        }) {
+            return Some(Some(existing));
+        }
+
+        let suffix = match self.manifest_type {
+            KubeManifestType::Json => ".json",
+            KubeManifestType::Yaml => match self.extension.as_str() {
+                "yaml" => ".yaml",
+                _ => ".yml",
+            },
+        };
+        let (resource_name, is_file) = match name.strip_suffix(suffix) {
+            Some(_) if self.hide_manifest => return Some(None),
+            Some(resource_name) => (resource_name, true),
+            None if matches!(api.kind.as_str(), "Pod" | "ConfigMap" | "Secret") => (name, false),
+            None => return None,
+        };
+
+        let resource = self.find_api_resource(&api)?;
+        let obj = match self.kube_client.get_resource(
+            api.namespace.clone().unwrap_or_default().as_str(),
+            resource,
+            resource_name,
+        ) {
+            Ok(Some(obj)) => obj,
+            Ok(None) => return Some(None),
+            Err(_) => return None,
+        };
+
+        let n = KubeResourceNode::from(&obj, &api.kind);
+        let node = match is_file {
+            true => KubeFileNode::ResourceFile(n),
+            false => KubeFileNode::ResourceDirectory(n),
+        };
+        let child = self.arena_two.add(node, Some(id));
+
+        Some(self.get_file(child.into()))
     }
 
     pub fn get_file(&self, inode: u64) -> Option<(String, FileAttr)> {
         let id = NodeId::new(inode);
         match self.arena_two.get(&id) {
             Some(node) => Some((
-                node.payload.get_file_name(),
+                self.get_file_name(&node.payload),
                 self.map_kube_file_to_attr(node),
             )),
             _ => None,
         }
     }
 
-    pub fn get_kube_manifest(&self, inode: u64) -> anyhow::Result<String> {
+    pub fn get_kube_manifest(&self, inode: u64) -> anyhow::Result<Vec<u8>> {
         let id = NodeId::new(inode);
 
         if let Some(node) = self.arena_two.get(&id) {
             if let KubeFileNode::ResourceFile(m) = &node.payload {
-                // let uuid = m.uuid;
-                // TODO: Grab the yaml or json contents of the k8s resource
-                return Ok("".into());
+                return Ok(self.get_manifest_file(m)?.to_string().into_bytes());
             } else {
                 return Err(anyhow::Error::msg("Not a manifest file!"));
             }
@@ -266,203 +997,4156 @@ impl KubeVirtualFs {
         Err(anyhow::Error::msg("Inode not found!"))
     }
 
-    pub fn list_files_two(&mut self, inode: u64) -> Option<Vec<(String, FileAttr)>> {
-        self.sync_leafs_for_inode(inode);
+    fn get_manifest_file(&self, node: &KubeResourceNode) -> anyhow::Result<KubeManifestFile> {
+        let mut data = self.get_resource_object(node)?;
 
-        let result: Option<Vec<(String, FileAttr)>> = self
-            .arena_two
-            .get_children(&NodeId::new(inode))
-            .map(|nodes| {
-                nodes
-                    .iter()
-                    .map(|n| (n.payload.get_file_name(), self.map_kube_file_to_attr(n)))
-                    .collect()
+        if self.decompress && is_helm_release(&data) {
+            if let Err(err) = decode_helm_release(&mut data) {
+                warn!("Unable to decode helm release {}: {}", node.name, err);
+            }
+        }
+
+        Ok(KubeManifestFile {
+            name: node.name.clone(),
+            file_type: self.manifest_type.clone(),
+            json_style: self.json_style.clone(),
+            data,
+            max_bytes: self.max_manifest_bytes,
+        })
+    }
+
+    /// Contents of the files that hold text, see `read_file` for the ones that may not.
+    fn get_text_contents(&self, inode: u64) -> anyhow::Result<String> {
+        let id = NodeId::new(inode);
+
+        match self.arena_two.get(&id).map(|n| &n.payload) {
+            Some(KubeFileNode::DiffFile(r)) => {
+                let obj = self.get_resource_object(r)?;
+                Ok(format_applied_diff(&obj)?)
+            }
+            Some(KubeFileNode::ScaleFile(r)) => self.get_scale(r),
+            Some(KubeFileNode::ContainerImageFile(pod, container)) => {
+                let obj = self.get_resource_object(pod)?;
+                let image = container_spec(&obj, container)
+                    .and_then(|c| c["image"].as_str())
+                    .ok_or(anyhow::Error::msg("Container not found!"))?;
+                Ok(format!("{}\n", image))
+            }
+            Some(KubeFileNode::ContainerStatusFile(pod, container)) => {
+                let obj = self.get_resource_object(pod)?;
+                Ok(format_container_status(&obj, container))
+            }
+            Some(KubeFileNode::PodSummaryFile(pod)) => {
+                let obj = self.get_resource_object(pod)?;
+                Ok(format_pod_summary(&obj))
+            }
+            Some(KubeFileNode::EventsFile(r)) => {
+                let namespace = r.namespace.clone().unwrap_or_default();
+                let events = self.kube_client.list_events(&namespace, &r.uuid)?;
+                Ok(format_events(events))
+            }
+            Some(KubeFileNode::QuotaFile(namespace)) => {
+                let (quotas, limit_ranges) = self.list_quotas(&namespace.name)?;
+                Ok(format_quota(&quotas, &limit_ranges))
+            }
+            Some(KubeFileNode::TableFile(api)) => self.get_resource_table(api),
+            Some(KubeFileNode::ReadmeFile(api)) => self.get_readme(api),
+            Some(KubeFileNode::ExplainFile(api)) => Ok(self.get_explain(api)?.unwrap_or_default()),
+            Some(KubeFileNode::FlushFile) => Ok(String::new()),
+            Some(KubeFileNode::InodesFile) => Ok(self.format_inodes()),
+            Some(KubeFileNode::IndexFile) => self.format_index(),
+            Some(KubeFileNode::ApplyResultFile) => Ok(self.apply_result.clone()),
+            Some(KubeFileNode::PatchResultFile(r)) => Ok(self
+                .patch_results
+                .iter()
+                .find(|(uid, _)| *uid == r.uuid)
+                .map(|(_, result)| result.clone())
+                .unwrap_or_default()),
+            Some(KubeFileNode::VersionFile) => {
+                let server = self.kube_client.cluster_info()?;
+                Ok(format!(
+                    "kubefs {}\nkubernetes {}\n",
+                    VERSION, server.git_version
+                ))
+            }
+            Some(KubeFileNode::ConnectionFile) => Ok(format_connection(
+                self.kube_client.connection(),
+                self.kube_client.current_context(),
+            )),
+            Some(KubeFileNode::ErrorsFile) => {
+                Ok(format_api_errors(&self.kube_client.recent_errors()))
+            }
+            Some(KubeFileNode::MetaFile) => {
+                let directory = self
+                    .arena_two
+                    .get(&id)
+                    .and_then(|n| n.parent_id.clone())
+                    .ok_or(anyhow::Error::msg("Directory not found!"))?;
+                Ok(self.format_meta(&directory))
+            }
+            Some(KubeFileNode::ResourcesFile) => Ok(format_api_resources(&self.api_resources)),
+            Some(KubeFileNode::ProjectionFile(r, path)) => self.get_projection(r, path),
+            Some(KubeFileNode::OwnedFieldsFile(r, manager)) => self.get_owned_fields(r, manager),
+            Some(KubeFileNode::VersionedResourceFile(r, version)) => {
+                let resource = self.find_api_resource_by_kind(&r.kind)?;
+                let data = self
+                    .kube_client
+                    .get_resource_at_version(
+                        r.namespace.clone().unwrap_or_default().as_str(),
+                        resource,
+                        r.name.as_str(),
+                        version,
+                    )?
+                    .ok_or(anyhow::Error::msg("Resource version not found!"))?;
+
+                Ok(KubeManifestFile {
+                    name: r.name.clone(),
+                    file_type: self.manifest_type.clone(),
+                    json_style: self.json_style.clone(),
+                    data,
+                    max_bytes: self.max_manifest_bytes,
+                }
+                .to_string())
+            }
+            Some(KubeFileNode::NodeMetricsFile) => {
+                let metrics = self.kube_client.node_metrics()?;
+                Ok(format_node_metrics(&metrics))
+            }
+            Some(KubeFileNode::ApiServicesFile) => {
+                let services = self.kube_client.list_api_services()?;
+                Ok(format_api_services(&services))
+            }
+            Some(KubeFileNode::PodMetricsFile(pod)) => {
+                let namespace = pod.namespace.clone().unwrap_or_default();
+                let metrics = self
+                    .kube_client
+                    .pod_metrics(namespace.as_str(), pod.name.as_str())?;
+                Ok(format_pod_metrics(&metrics))
+            }
+            Some(_) => Err(anyhow::Error::msg("Not a text file!")),
+            None => Err(anyhow::Error::msg("Inode not found!")),
+        }
+    }
+
+    /// The resource behind the manifest `file_name` in the directory `parent`, along with the
+    /// node the views of the manifest hang off. With `-o hide_manifest` the manifest isn't in the
+    /// tree, so the resource comes from the cluster and its views hang off the directory.
+    fn find_manifest(
+        &mut self,
+        parent: u64,
+        file_name: &str,
+    ) -> anyhow::Result<Option<(NodeId, KubeResourceNode)>> {
+        if !self.hide_manifest {
+            let file = match self.get_file_from_parent_by_name_two(parent, file_name)? {
+                Some((_, file)) => NodeId::new(file.ino),
+                None => return Ok(None),
+            };
+
+            return Ok(match self.arena_two.get(&file).map(|n| &n.payload) {
+                Some(KubeFileNode::ResourceFile(r)) => Some((file, r.clone())),
+                _ => None,
             });
+        }
+
+        let id = NodeId::new(parent);
+        let api = match self.arena_two.get(&id).map(|n| &n.payload) {
+            Some(KubeFileNode::ApiResourceDirectory(api))
+            | Some(KubeFileNode::NamespaceDirectory(api))
+                if api.namespace.is_some() =>
+            {
+                api.clone()
+            }
+            _ => return Ok(None),
+        };
+        let name = match self.resource_name_from_file(file_name) {
+            Ok(name) => name,
+            Err(_) => return Ok(None),
+        };
 
-        println!("Files for {} are {:?}", inode, result);
-        result
+        let obj = match self.lazy {
+            true => {
+                let resource = self
+                    .find_api_resource(&api)
+                    .ok_or(anyhow::Error::msg("Unknown api resource!"))?;
+                let namespace = api.namespace.clone().unwrap_or_default();
+                self.kube_client
+                    .get_resource(namespace.as_str(), resource, name)?
+            }
+            false => self
+                .list_api_resource_objects(&api)?
+                .into_iter()
+                .find(|o| o.name_any() == name),
+        };
+
+        Ok(obj.map(|o| (id, KubeResourceNode::from(&o, &api.kind))))
     }
 
-    fn map_kube_file_to_attr(&self, node: &Node<KubeFileNode>) -> FileAttr {
-        match &node.payload {
-            KubeFileNode::Virtual(_)
-            | KubeFileNode::Context(_)
-            | KubeFileNode::ApiResourceDirectory(_)
-            | KubeFileNode::ResourceDirectory(_) => FileAttr {
-                ino: node.id.clone().into(),
-                size: 0,
-                blocks: 0,
-                atime: self.startup,
-                mtime: self.startup,
-                ctime: self.startup,
-                crtime: self.startup,
-                kind: FileType::Directory,
-                perm: 0o755,
-                nlink: 1,
-                uid: 1000,
-                gid: 1000,
-                rdev: 0,
-                blksize: 512,
-                flags: 0,
-            },
-            KubeFileNode::ResourceFile(file) => FileAttr {
-                ino: node.id.clone().into(),
-                size: 10000,
-                blocks: 0,
-                atime: self.startup,
-                mtime: self.startup,
-                ctime: self.startup,
-                crtime: self.startup,
-                kind: FileType::RegularFile,
-                perm: 0o655,
-                nlink: 1,
-                uid: 1000,
-                gid: 1000,
-                rdev: 0,
-                blksize: 512,
-                flags: 0,
-            },
-            KubeFileNode::ClusterInfoFile | KubeFileNode::LogFile(_) => FileAttr {
-                ino: node.id.clone().into(),
-                size: 10000,
-                blocks: 0,
-                atime: self.startup,
-                mtime: self.startup,
-                ctime: self.startup,
-                crtime: self.startup,
-                kind: FileType::RegularFile,
-                perm: 0o655,
-                nlink: 1,
-                uid: 10000,
-                gid: 1000,
-                rdev: 0,
-                blksize: 512,
-                flags: 0,
-            },
+    /// Looks up `<manifest>?<path>` in a directory, creating the projection underneath the
+    /// manifest file. Fails when the path can't be parsed.
+    pub fn get_projection_file(
+        &mut self,
+        parent: u64,
+        name: &str,
+    ) -> anyhow::Result<Option<(String, FileAttr)>> {
+        let (file_name, path) = name.split_once('?').unwrap_or((name, ""));
+
+        if !is_json_path(path) {
+            return Err(anyhow::Error::msg(format!("Invalid path {}", path)));
         }
+
+        let (file, projection) = match self.find_manifest(parent, file_name)? {
+            Some((file, r)) => (file, KubeFileNode::ProjectionFile(r, path.into())),
+            None => return Ok(None),
+        };
+
+        let existing = self
+            .arena_two
+            .get_children(&file)
+            .unwrap_or_default()
+            .iter()
+            .find(|n| n.payload == projection)
+            .map(|n| n.id.clone());
+
+        let id = match existing {
+            Some(id) => id,
+            None => self.arena_two.add(projection, Some(file)),
+        };
+
+        Ok(self.get_file(id.into()))
     }
 
-    fn get_leafs_for_node(&self, node: &Node<KubeFileNode>) -> Vec<KubeFileNode> {
-        match &node.payload {
-            KubeFileNode::Context(_) => {
-                let mut items = Vec::new();
-                items.push(KubeFileNode::Virtual(String::from(".")));
-                items.push(KubeFileNode::Virtual(String::from("..")));
+    /// Looks up `<name>.owned.<manager>.<ext>` in a directory, creating the view underneath the
+    /// manifest file. Managers that don't own any fields of the resource don't have a view.
+    pub fn get_owned_fields_file(
+        &mut self,
+        parent: u64,
+        name: &str,
+    ) -> anyhow::Result<Option<(String, FileAttr)>> {
+        let (resource_name, manager) = self
+            .resource_name_from_file(name)?
+            .rsplit_once(".owned.")
+            .filter(|(n, m)| !n.is_empty() && !m.is_empty())
+            .ok_or(anyhow::Error::msg(format!(
+                "Expected a file name like <name>.owned.<manager> instead of {}",
+                name
+            )))?;
+
+        let file_name = name.replacen(&format!(".owned.{}", manager), "", 1);
+        let (file, owned) = match self.find_manifest(parent, &file_name)? {
+            Some((file, r)) if r.name == resource_name => {
+                (file, KubeFileNode::OwnedFieldsFile(r, manager.into()))
+            }
+            _ => return Ok(None),
+        };
 
-                items.push(KubeFileNode::ClusterInfoFile);
+        let existing = self
+            .arena_two
+            .get_children(&file)
+            .unwrap_or_default()
+            .iter()
+            .find(|n| n.payload == owned)
+            .map(|n| n.id.clone());
 
-                let namespaces = self.kube_client.list_namespaces().unwrap();
+        let id = match existing {
+            Some(id) => id,
+            None => self.arena_two.add(owned, Some(file)),
+        };
 
-                for namespace in namespaces {
-                    let uuid = namespace.uid().unwrap();
-                    let name = namespace.name_any();
-                    let n = KubeResourceNode::new(uuid.as_str(), name.as_str(), "Namespace".into());
-                    items.push(KubeFileNode::ResourceDirectory(n.clone()));
-                    items.push(KubeFileNode::ResourceFile(n));
-                }
+        if self.read_file(id.clone().into()).is_err() {
+            self.arena_two.delete_node(id);
+            return Ok(None);
+        }
 
-                items
+        Ok(self.get_file(id.into()))
+    }
+
+    /// Looks up `<name>@<version>.<ext>` in a directory, the manifest of a resource as of an
+    /// older resourceVersion. Fails when the name can't be parsed.
+    pub fn get_versioned_file(
+        &mut self,
+        parent: u64,
+        name: &str,
+    ) -> anyhow::Result<Option<(String, FileAttr)>> {
+        let (resource_name, version) = self
+            .resource_name_from_file(name)?
+            .rsplit_once('@')
+            .filter(|(n, v)| !n.is_empty() && !v.is_empty())
+            .ok_or(anyhow::Error::msg(format!(
+                "Expected a file name like <name>@<version> instead of {}",
+                name
+            )))?;
+
+        let file_name = name.replacen(&format!("@{}", version), "", 1);
+        let (file, versioned) = match self.find_manifest(parent, &file_name)? {
+            Some((file, r)) if r.name == resource_name && r.kind != "Namespace" => {
+                (file, KubeFileNode::VersionedResourceFile(r, version.into()))
             }
-            KubeFileNode::ResourceDirectory(dir) => {
-                let mut items = Vec::new();
-                items.push(KubeFileNode::Virtual(String::from(".")));
-                items.push(KubeFileNode::Virtual(String::from("..")));
+            _ => return Ok(None),
+        };
 
-                println!("Rendering Resource Directory {:?}", dir);
+        let existing = self
+            .arena_two
+            .get_children(&file)
+            .unwrap_or_default()
+            .iter()
+            .find(|n| n.payload == versioned)
+            .map(|n| n.id.clone());
 
-                match dir.kind.as_str() {
-                    "Namespace" => {
-                        let scoped: Vec<&ApiResource> = self
-                            .api_resources
-                            .iter()
-                            .filter(|(_, c)| c.scope == Scope::Namespaced)
-                            .map(|(a, _)| a)
-                            .collect();
-
-                        for api in scoped {
-                            let n = KubeApiResourceNode {
-                                namespace: Some(dir.name.clone()),
-                                group: api.group.clone(),
-                                kind: api.kind.clone(),
-                                version: api.version.clone(),
-                                plural: api.plural.clone(),
-                            };
-                            items.push(KubeFileNode::ApiResourceDirectory(n));
-                        }
-                    }
-                    _ => {}
-                }
-                items
+        let id = match existing {
+            Some(id) => id,
+            None => self.arena_two.add(versioned, Some(file)),
+        };
+
+        // Versions that are neither current nor seen by a watch don't exist as far as we know.
+        if self.read_file(id.clone().into()).is_err() {
+            self.arena_two.delete_node(id);
+            return Ok(None);
+        }
+
+        Ok(self.get_file(id.into()))
+    }
+
+    /// The value at `path` in the manifest, scalars as is and anything else serialized in the
+    /// configured format. Paths that don't resolve give an empty file.
+    fn get_projection(&self, node: &KubeResourceNode, path: &str) -> anyhow::Result<String> {
+        let manifest = self.get_manifest_file(node)?;
+        let mut obj = manifest.data;
+        obj.metadata.managed_fields = None;
+        let json = serde_json::to_value(&obj)?;
+
+        let value = match json_path(&json, path) {
+            Some(value) => value,
+            None => return Ok(String::new()),
+        };
+
+        match (value, &self.manifest_type, &self.json_style) {
+            (serde_json::Value::String(s), _, _) => Ok(format!("{}\n", s)),
+            (_, KubeManifestType::Yaml, _) => Ok(serde_yaml::to_string(value)?),
+            (_, KubeManifestType::Json, KubeJsonStyle::Pretty) => {
+                Ok(format!("{}\n", serde_json::to_string_pretty(value)?))
             }
-            KubeFileNode::ApiResourceDirectory(api) => {
-                let mut items = Vec::new();
-                items.push(KubeFileNode::Virtual(String::from(".")));
-                items.push(KubeFileNode::Virtual(String::from("..")));
+            (_, KubeManifestType::Json, KubeJsonStyle::Compact) => {
+                Ok(format!("{}\n", serde_json::to_string(value)?))
+            }
+        }
+    }
 
-                let (resource, _) = self
-                    .api_resources
-                    .iter()
-                    .find(|(a, _)| {
-                        a.group == api.group && a.kind == api.kind && a.version == api.version
-                    })
-                    .unwrap();
+    /// The manifest cut down to the fields `manager` owns, with the identifying fields an apply
+    /// configuration needs. Fails when the manager owns nothing. The managedFields are only
+    /// dropped when rendering manifests, so the objects in the cache still have them.
+    fn get_owned_fields(&self, node: &KubeResourceNode, manager: &str) -> anyhow::Result<String> {
+        let obj = self.get_resource_object(node)?;
 
-                let objs = self
-                    .kube_client
-                    .list_resources(api.namespace.clone().unwrap().as_str(), resource)
-                    .unwrap();
+        let mut fields = serde_json::Map::new();
+        let mut found = false;
+        for entry in obj.metadata.managed_fields.iter().flatten() {
+            if entry.manager.as_deref() != Some(manager) {
+                continue;
+            }
+            found = true;
+            if let Some(serde_json::Value::Object(owned)) = entry.fields_v1.as_ref().map(|f| &f.0) {
+                merge_field_sets(&mut fields, owned);
+            }
+        }
 
-                for obj in &objs {
-                    items.push(KubeFileNode::ResourceFile(KubeResourceNode::from(
-                        obj, &api.kind,
-                    )));
-                }
+        if !found {
+            return Err(anyhow::Error::msg(format!(
+                "{} doesn't own any fields of {}",
+                manager, node.name
+            )));
+        }
 
-                items
+        let mut live = obj.clone();
+        live.metadata.managed_fields = None;
+        let live = serde_json::to_value(&live)?;
+
+        let mut owned = project_field_set(&live, &fields).unwrap_or_else(|| json!({}));
+        owned["apiVersion"] = live["apiVersion"].clone();
+        owned["kind"] = live["kind"].clone();
+        owned["metadata"]["name"] = live["metadata"]["name"].clone();
+        if live["metadata"]["namespace"].is_string() {
+            owned["metadata"]["namespace"] = live["metadata"]["namespace"].clone();
+        }
+
+        match (&self.manifest_type, &self.json_style) {
+            (KubeManifestType::Yaml, _) => Ok(serde_yaml::to_string(&owned)?),
+            (KubeManifestType::Json, KubeJsonStyle::Pretty) => {
+                Ok(format!("{}\n", serde_json::to_string_pretty(&owned)?))
+            }
+            (KubeManifestType::Json, KubeJsonStyle::Compact) => {
+                Ok(format!("{}\n", serde_json::to_string(&owned)?))
             }
-            _ => Vec::new(),
         }
     }
 
-    fn sync_leafs_for_inode(&mut self, inode: u64) {
-        println!("syncing leafs for node {}", inode);
-        let id = NodeId::new(inode);
-        let node = self.arena_two.get(&id);
+    /// Logs of the first container of a pod, or the one named by the
+    /// `kubectl.kubernetes.io/default-container` annotation, same as `kubectl logs` picks.
+    fn get_pod_logs(&self, pod: &KubeResourceNode, timestamps: bool) -> anyhow::Result<Vec<u8>> {
+        let obj = self.get_resource_object(pod)?;
+        let container = obj
+            .annotations()
+            .get("kubectl.kubernetes.io/default-container")
+            .cloned()
+            .or_else(|| {
+                obj.data["spec"]["containers"][0]["name"]
+                    .as_str()
+                    .map(String::from)
+            });
 
-        if node.is_none() {
-            return;
+        self.get_container_logs(pod, container, timestamps, false)
+    }
+
+    /// Logs of a container of a pod, or of its previous instance when it restarted.
+    fn get_container_logs(
+        &self,
+        pod: &KubeResourceNode,
+        container: Option<String>,
+        timestamps: bool,
+        previous: bool,
+    ) -> anyhow::Result<Vec<u8>> {
+        let params = LogParams {
+            container,
+            previous,
+            timestamps: timestamps || self.log_timestamps,
+            since_seconds: self.log_since.map(|since| since.as_secs() as i64),
+            ..LogParams::default()
+        };
+
+        self.kube_client.get_pod_logs(
+            pod.namespace.clone().unwrap_or_default().as_str(),
+            pod.name.as_str(),
+            &params,
+        )
+    }
+
+    /// Value of a ConfigMap key, or the decoded value of a Secret key.
+    fn get_data_key(&self, node: &KubeResourceNode, key: &str) -> anyhow::Result<Vec<u8>> {
+        let obj = self.get_resource_object(node)?;
+        data_key_bytes(&obj, &node.kind, key)
+    }
+
+    /// Contents of a file as bytes, what reads are served from. The binary data of ConfigMaps and
+    /// Secrets, logs and pending writes are passed on as is, they need not be valid utf-8.
+    pub fn read_file(&self, inode: u64) -> anyhow::Result<Vec<u8>> {
+        match self.arena_two.get(&NodeId::new(inode)).map(|n| &n.payload) {
+            Some(KubeFileNode::ResourceFile(_)) => self.get_kube_manifest(inode),
+            Some(KubeFileNode::DataKeyFile(r, key)) => self.get_data_key(r, key),
+            Some(KubeFileNode::LogFile(pod, timestamps)) => self.get_pod_logs(pod, *timestamps),
+            Some(KubeFileNode::ContainerLogFile(pod, container, previous)) => {
+                self.get_container_logs(pod, Some(container.clone()), false, *previous)
+            }
+            Some(KubeFileNode::NewResourceFile(..))
+            | Some(KubeFileNode::ApplyFile(_))
+            | Some(KubeFileNode::PatchFile(_))
+            | Some(KubeFileNode::ConfirmDeleteFile(_)) => {
+                Ok(self.write_buffers.get(&inode).cloned().unwrap_or_default())
+            }
+            _ => Ok(self.get_text_contents(inode)?.into_bytes()),
+        }
+    }
+
+    /// Files that are expensive to render get a made up size, they're read past the page cache so
+    /// contents longer than that aren't cut off.
+    pub fn is_direct_io(&self, inode: u64) -> bool {
+        matches!(
+            self.arena_two.get(&NodeId::new(inode)).map(|n| &n.payload),
+            Some(KubeFileNode::ClusterInfoFile)
+                | Some(KubeFileNode::ErrorsFile)
+                | Some(KubeFileNode::ResourcesFile)
+                | Some(KubeFileNode::PodSummaryFile(_))
+                | Some(KubeFileNode::QuotaFile(_))
+                | Some(KubeFileNode::EventsFile(_))
+                | Some(KubeFileNode::NodeMetricsFile)
+                | Some(KubeFileNode::ApiServicesFile)
+                | Some(KubeFileNode::PodMetricsFile(_))
+                | Some(KubeFileNode::ScaleFile(_))
+                | Some(KubeFileNode::ExplainFile(_))
+                | Some(KubeFileNode::VersionFile)
+                | Some(KubeFileNode::LogFile(..))
+                | Some(KubeFileNode::ContainerLogFile(..))
+                | Some(KubeFileNode::TableFile(_))
+        )
+    }
+
+    /// With `-o refresh_on_open` a manifest that gets opened is fetched again, so reads see the
+    /// current resource rather than the one in the cached listing. Errors when it's gone.
+    pub fn open_file(&self, inode: u64) -> anyhow::Result<()> {
+        if !self.refresh_on_open {
+            return Ok(());
         }
 
-        let node = node.unwrap();
+        self.refresh_file(inode)
+    }
+
+    /// `touch` on a manifest fetches the resource again, updating the cached listing it's part of
+    /// without changing anything in the cluster. Touching any other file does nothing. Only
+    /// reachable on a read-write mount, with `-o rw` or `-o touch_refresh`.
+    pub fn touch_file(&self, inode: u64) -> anyhow::Result<()> {
+        self.refresh_file(inode)
+    }
+
+    /// Fetches the resource of a manifest again, errors when it's gone.
+    fn refresh_file(&self, inode: u64) -> anyhow::Result<()> {
+        let node = match self.arena_two.get(&NodeId::new(inode)).map(|n| &n.payload) {
+            Some(KubeFileNode::ResourceFile(r)) if r.namespace.is_some() => r,
+            _ => return Ok(()),
+        };
+
+        let resource = self.find_api_resource_by_kind(&node.kind)?;
+        let namespace = node.namespace.clone().unwrap_or_default();
+
+        match self
+            .kube_client
+            .refresh_resource(namespace.as_str(), resource, node.name.as_str())?
+        {
+            Some(obj) if KubeResourceNode::identity(&obj, &node.kind) == node.uuid => Ok(()),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "Resource not found!").into()),
+        }
+    }
+
+    pub fn is_writable(&self, inode: u64) -> bool {
+        match self.arena_two.get(&NodeId::new(inode)).map(|n| &n.payload) {
+            Some(KubeFileNode::FlushFile) => true,
+            Some(KubeFileNode::DataKeyFile(r, _)) => self.is_kind_writable(&r.kind),
+            Some(KubeFileNode::NewResourceFile(api, _)) => self.is_kind_writable(&api.kind),
+            Some(KubeFileNode::ApplyFile(_)) => self.read_write,
+            Some(KubeFileNode::ConfirmDeleteFile(_)) => self.allow_recursive_delete,
+            Some(KubeFileNode::PatchFile(r)) => self.is_kind_writable(&r.kind),
+            _ => false,
+        }
+    }
+
+    /// With `-o rw` every kind can be written to, with `-o rw=<kind>` only the listed ones.
+    fn is_kind_writable(&self, kind: &str) -> bool {
+        if !self.read_write {
+            return false;
+        }
+
+        let plural = self
+            .find_api_resource_by_kind(kind)
+            .ok()
+            .map(|r| r.plural.as_str());
+        self.writable_kinds.is_empty()
+            || self
+                .writable_kinds
+                .iter()
+                .any(|k| *k == kind.to_lowercase() || Some(k.as_str()) == plural)
+    }
+
+    pub fn write_file(&mut self, inode: u64, offset: i64, data: &[u8]) -> anyhow::Result<usize> {
+        let buffer = self.get_write_buffer(inode)?;
+        let (start, end) = (offset as usize, offset as usize + data.len());
+
+        if buffer.len() < end {
+            buffer.resize(end, 0);
+        }
+        buffer[start..end].copy_from_slice(data);
+
+        Ok(data.len())
+    }
+
+    pub fn truncate_file(&mut self, inode: u64, size: u64) -> anyhow::Result<()> {
+        self.get_write_buffer(inode)?.resize(size as usize, 0);
+        Ok(())
+    }
+
+    /// Writes the buffered contents of a file back to the cluster, files that weren't written
+    /// to since the last flush are left alone.
+    pub fn flush_file(&mut self, inode: u64) -> anyhow::Result<()> {
+        let buffer = match self.write_buffers.remove(&inode) {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+
+        match self.arena_two.get(&NodeId::new(inode)).map(|n| &n.payload) {
+            Some(KubeFileNode::DataKeyFile(r, key)) => self.write_data_key(r, key, buffer),
+            Some(KubeFileNode::FlushFile) => {
+                info!("Flushing all caches");
+                self.kube_client.clear_cache();
+                Ok(())
+            }
+            Some(KubeFileNode::PatchFile(r)) if buffer.is_empty() => {
+                debug!("Nothing to patch {} {} with yet", r.kind, r.name);
+                Ok(())
+            }
+            Some(KubeFileNode::PatchFile(r)) => {
+                let node = r.clone();
+                self.patch_json(&node, &buffer)
+            }
+            Some(KubeFileNode::ConfirmDeleteFile(namespace)) => {
+                info!("Confirmed the deletion of namespace {}", namespace);
+                Ok(())
+            }
+            Some(KubeFileNode::ApplyFile(name)) if buffer.is_empty() => {
+                debug!("Nothing to apply for {} yet", name);
+                Ok(())
+            }
+            Some(KubeFileNode::ApplyFile(name)) => {
+                let name = name.clone();
+                // Once applied the manifest is gone, `last-result` tells how it went.
+                self.arena_two.delete_node(NodeId::new(inode));
+                self.apply_manifests(&name, &buffer)
+            }
+            Some(KubeFileNode::NewResourceFile(api, name)) if buffer.is_empty() => {
+                debug!("Nothing to create for {} {} yet", api.kind, name);
+                Ok(())
+            }
+            Some(KubeFileNode::NewResourceFile(api, name)) => {
+                let (api, name) = (api.clone(), name.clone());
+                self.create_resource(inode, &api, &name, buffer)
+            }
+            _ => Err(anyhow::Error::msg("File is not writable!")),
+        }
+    }
+
+    /// Creates an empty manifest in a kind directory. It's only applied to the cluster once
+    /// its contents are flushed, which is when close(2) can still report a bad manifest.
+    pub fn create_file(&mut self, parent: u64, name: &str) -> anyhow::Result<(String, FileAttr)> {
+        let id = NodeId::new(parent);
+        let api = match self.arena_two.get(&id).map(|n| &n.payload) {
+            Some(KubeFileNode::ApplyDirectory) => {
+                return self.add_new_file(id, KubeFileNode::ApplyFile(name.into()), name)
+            }
+            Some(KubeFileNode::ResourceDirectory(dir))
+                if dir.kind == "Namespace"
+                    && self.allow_recursive_delete
+                    && self.is_kind_writable("Namespace")
+                    && name == CONFIRM_DELETE_FILE =>
+            {
+                let node = KubeFileNode::ConfirmDeleteFile(dir.name.clone());
+                return self.add_new_file(id, node, name);
+            }
+            Some(KubeFileNode::ApiResourceDirectory(api))
+            | Some(KubeFileNode::NamespaceDirectory(api))
+                if api.namespace.is_some() && self.is_kind_writable(&api.kind) =>
+            {
+                api.clone()
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "Resources can only be created in a writable kind directory with -o rw",
+                )
+                .into())
+            }
+        };
+
+        self.resource_name_from_file(name)?;
+
+        self.add_new_file(id, KubeFileNode::NewResourceFile(api, name.into()), name)
+    }
 
-        let new_leaf = self.get_leafs_for_node(node);
+    /// Deletes the namespace of a namespace directory with `-o allow_recursive_delete`, once a
+    /// `.confirm` file was created in it. Namespaces have to be writable as well. Every other
+    /// directory holds files that can't be removed, so it's never empty.
+    pub fn remove_directory(&mut self, parent: u64, name: &str) -> anyhow::Result<()> {
+        let child = self
+            .arena_two
+            .get_children(&NodeId::new(parent))
+            .unwrap_or_default()
+            .into_iter()
+            .find(|n| self.get_file_name(&n.payload) == name)
+            .map(|n| (n.id.clone(), n.payload.clone()));
+
+        let (id, namespace) = match child {
+            Some((id, KubeFileNode::ResourceDirectory(dir))) if dir.kind == "Namespace" => {
+                (id, dir.name)
+            }
+            Some(_) => return Err(io::Error::from(io::ErrorKind::DirectoryNotEmpty).into()),
+            None => return Err(io::Error::from(io::ErrorKind::NotFound).into()),
+        };
+
+        if !self.allow_recursive_delete {
+            return Err(io::Error::from(io::ErrorKind::DirectoryNotEmpty).into());
+        }
+
+        if !self.is_kind_writable("Namespace") {
+            return Err(io::Error::from(io::ErrorKind::PermissionDenied).into());
+        }
 
-        let old_leaf: Vec<(NodeId, KubeFileNode)> = self
+        let confirmations: Vec<NodeId> = self
             .arena_two
             .get_children(&id)
             .unwrap_or_default()
             .iter()
-            .map(|n| (n.id.clone(), n.payload.clone()))
+            .filter(|n| matches!(n.payload, KubeFileNode::ConfirmDeleteFile(_)))
+            .map(|n| n.id.clone())
             .collect();
+        if confirmations.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Create {}/{} to confirm deleting the namespace",
+                    namespace, CONFIRM_DELETE_FILE
+                ),
+            )
+            .into());
+        }
+
+        // A confirmation is good for a single attempt, whether the delete goes through or not.
+        for confirmation in confirmations {
+            self.write_buffers.remove(&confirmation.clone().into());
+            self.arena_two.delete_node(confirmation);
+        }
+
+        warn!("Deleting namespace {} and everything in it", namespace);
+        self.kube_client.delete_namespace(&namespace)?;
+        let removed = self.arena_two.delete_node(id).unwrap_or_default();
+        self.forget_sync_states(removed);
 
-        let add_nodes: Vec<KubeFileNode> = new_leaf
+        Ok(())
+    }
+
+    /// Adds a file that only exists locally until it's flushed, or reuses the one by that name.
+    fn add_new_file(
+        &mut self,
+        id: NodeId,
+        node: KubeFileNode,
+        name: &str,
+    ) -> anyhow::Result<(String, FileAttr)> {
+        let existing = self
+            .arena_two
+            .get_children(&id)
+            .unwrap_or_default()
             .iter()
-            .filter(|n| !old_leaf.iter().any(|(_, o)| o == *n))
-            .map(|n| n.clone())
-            .collect();
+            .find(|n| self.get_file_name(&n.payload) == name)
+            .map(|n| n.id.clone());
+
+        let child = match existing {
+            Some(child) => child,
+            None => self.arena_two.add(node, Some(id)),
+        };
+        self.write_buffers.insert(child.clone().into(), Vec::new());
+
+        self.get_file(child.into())
+            .ok_or(anyhow::Error::msg("File not found!"))
+    }
+
+    /// Name of the resource a manifest file is for, `foo.yml` holds `foo`.
+    fn resource_name_from_file<'a>(&self, name: &'a str) -> anyhow::Result<&'a str> {
+        let extension = match self.manifest_type {
+            KubeManifestType::Json => "json",
+            KubeManifestType::Yaml => self.extension.as_str(),
+        };
+
+        name.strip_suffix(&format!(".{}", extension))
+            .filter(|n| !n.is_empty())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Expected a file name like <name>.{}", extension),
+                )
+                .into()
+            })
+    }
+
+    /// Parses a new manifest and creates it, after which the file is a regular manifest.
+    fn create_resource(
+        &mut self,
+        inode: u64,
+        api: &KubeApiResourceNode,
+        file_name: &str,
+        buffer: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+        // Json is valid yaml, so this covers either format.
+        let mut obj: DynamicObject = serde_yaml::from_slice(&buffer)
+            .map_err(|e| invalid(format!("Unable to parse {}: {}", file_name, e)))?;
+
+        let name = self.resource_name_from_file(file_name)?;
+        if obj.metadata.name.as_deref() != Some(name) {
+            return Err(invalid(format!("Expected metadata.name to be {}", name)).into());
+        }
+
+        if obj.types.as_ref().map(|t| t.kind.as_str()) != Some(api.kind.as_str()) {
+            return Err(invalid(format!("Expected kind to be {}", api.kind)).into());
+        }
+
+        let namespace = api.namespace.clone().unwrap_or_default();
+        match obj.metadata.namespace.as_deref() {
+            Some(ns) if ns != namespace => {
+                return Err(
+                    invalid(format!("Expected metadata.namespace to be {}", namespace)).into(),
+                )
+            }
+            _ => obj.metadata.namespace = Some(namespace.clone()),
+        }
+
+        let resource = self
+            .find_api_resource(api)
+            .ok_or(anyhow::Error::msg("Unknown api resource!"))?;
+        let created = self
+            .kube_client
+            .create_resource(namespace.as_str(), resource, &obj)?;
+
+        if let Some(node) = self.arena_two.get_mut(&NodeId::new(inode)) {
+            node.payload = KubeFileNode::ResourceFile(KubeResourceNode::from(&created, &api.kind));
+        }
+
+        Ok(())
+    }
+
+    /// Applies every document in a manifest dropped in `.apply`, like `kubectl apply -f`. A bad
+    /// document doesn't hold back the others, the outcome of each ends up in `last-result`.
+    fn apply_manifests(&mut self, file_name: &str, buffer: &[u8]) -> anyhow::Result<()> {
+        let text = String::from_utf8_lossy(buffer);
+
+        // The marker is a comment, applying what's left of the manifest would go through fine
+        // and drop whatever got cut off.
+        if text.contains(TRUNCATED_MARKER.trim()) {
+            self.apply_result = format!("{}: manifest was truncated by kubefs\n", file_name);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Truncated manifests can't be applied, see .apply/last-result",
+            )
+            .into());
+        }
+        let mut lines = Vec::new();
+        let mut failed = false;
+
+        for (i, document) in serde_yaml::Deserializer::from_str(&text).enumerate() {
+            let applied = serde_json::Value::deserialize(document)
+                .map_err(anyhow::Error::from)
+                .and_then(|value| match value.is_null() {
+                    true => Ok(None),
+                    false => self.apply_manifest(value).map(Some),
+                });
+
+            match applied {
+                Ok(Some(applied)) => lines.push(format!("{} applied", applied)),
+                Ok(None) => {}
+                Err(err) => {
+                    failed = true;
+                    lines.push(format!("{} document {}: {:#}", file_name, i + 1, err));
+                }
+            }
+        }
+
+        self.apply_result = format!("{}\n", lines.join("\n"));
+
+        match failed {
+            true => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unable to apply {}, see .apply/last-result", file_name),
+            )
+            .into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Applies the JSON patch written to the `.patch` file of a resource, `.patch-result` tells
+    /// how it went.
+    fn patch_json(&mut self, node: &KubeResourceNode, buffer: &[u8]) -> anyhow::Result<()> {
+        let resource = self.find_api_resource_by_kind(&node.kind)?.clone();
+        let namespace = node.namespace.clone().unwrap_or_default();
+
+        let patched = serde_json::from_slice::<json_patch::Patch>(buffer)
+            .context("Invalid JSON patch")
+            .and_then(|patch| {
+                self.kube_client.patch_resource(
+                    namespace.as_str(),
+                    &resource,
+                    node.name.as_str(),
+                    &Patch::Json(patch),
+                )
+            });
+
+        let result = match &patched {
+            Ok(obj) => format!(
+                "{}/{} in {} patched, now at resourceVersion {}\n",
+                resource.plural,
+                node.name,
+                namespace,
+                obj.resource_version().unwrap_or_default()
+            ),
+            Err(err) => format!("{:#}\n", err),
+        };
+        self.patch_results.retain(|(uid, _)| *uid != node.uuid);
+        self.patch_results.truncate(RECENT_PATCH_RESULTS - 1);
+        self.patch_results.push_front((node.uuid.clone(), result));
+
+        match patched {
+            Ok(_) => Ok(()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unable to patch {}, see .patch-result", node.name),
+            )
+            .into()),
+        }
+    }
+
+    /// Applies a single manifest, returning what got applied, e.g. `deployments/web in default`.
+    fn apply_manifest(&self, value: serde_json::Value) -> anyhow::Result<String> {
+        let mut obj: DynamicObject = serde_json::from_value(value)?;
+        let types = obj
+            .types
+            .clone()
+            .ok_or(anyhow::Error::msg("Missing apiVersion or kind"))?;
+        let name = obj
+            .metadata
+            .name
+            .clone()
+            .ok_or(anyhow::Error::msg("Missing metadata.name"))?;
 
-        let remove_inodes: Vec<NodeId> = old_leaf
+        let (resource, capabilities) = self
+            .api_resources
             .iter()
-            .filter(|(_, n)| !new_leaf.iter().any(|o| o == n))
-            .map(|(i, _)| i.clone())
-            .collect();
+            .find(|(r, _)| r.api_version == types.api_version && r.kind == types.kind)
+            .ok_or(anyhow::Error::msg(format!(
+                "Unknown kind {} {}",
+                types.api_version, types.kind
+            )))?;
+
+        if !self.is_kind_writable(&types.kind) {
+            return Err(anyhow::Error::msg(format!(
+                "{} is not writable",
+                resource.plural
+            )));
+        }
+
+        let namespace = match capabilities.scope {
+            Scope::Namespaced => {
+                let namespace = obj.metadata.namespace.clone().unwrap_or("default".into());
+                obj.metadata.namespace = Some(namespace.clone());
+                Some(namespace)
+            }
+            Scope::Cluster => None,
+        };
+
+        self.kube_client
+            .apply_resource(namespace.as_deref(), resource, &name, &obj)?;
+
+        Ok(match namespace {
+            Some(namespace) => format!("{}/{} in {}", resource.plural, name, namespace),
+            None => format!("{}/{}", resource.plural, name),
+        })
+    }
+
+    /// Pending writes are buffered per inode, starting from the current contents of the file.
+    fn get_write_buffer(&mut self, inode: u64) -> anyhow::Result<&mut Vec<u8>> {
+        if !self.write_buffers.contains_key(&inode) {
+            if !self.is_writable(inode) {
+                return Err(anyhow::Error::msg("File is not writable!"));
+            }
 
-        for inode in remove_inodes {
-            self.arena_two.delete_node(inode);
+            let contents = self.read_file(inode)?;
+            self.write_buffers.insert(inode, contents);
         }
 
-        for node in add_nodes {
-            self.arena_two.add(node, Some(id.clone()));
+        Ok(self.write_buffers.get_mut(&inode).expect("buffer exists"))
+    }
+
+    fn write_data_key(
+        &self,
+        node: &KubeResourceNode,
+        key: &str,
+        value: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let obj = self.get_resource_object(node)?;
+        let resource = self.find_api_resource_by_kind(&node.kind)?;
+        let patch = data_key_patch(&obj, &node.kind, key, value)?;
+
+        self.kube_client.patch_resource(
+            node.namespace.clone().unwrap_or_default().as_str(),
+            resource,
+            node.name.as_str(),
+            &Patch::Json(serde_json::from_value(patch)?),
+        )?;
+
+        Ok(())
+    }
+
+    /// Describes a kind for the `README` of its directory: its description when a CRD gives one,
+    /// how many resources of it there are and how old the oldest and newest of them are.
+    fn get_readme(&self, api: &KubeApiResourceNode) -> anyhow::Result<String> {
+        let resource = self
+            .find_api_resource(api)
+            .ok_or(anyhow::Error::msg("Unknown api resource!"))?;
+        let mut objs = self.list_api_resource_objects(api)?;
+        objs.sort_by_key(|o| o.creation_timestamp());
+
+        let mut out = format!("{} ({})\n\n", resource.kind, resource.api_version);
+        // Like the columns of the tables, descriptions are best effort. Reading CRDs may well
+        // not be allowed.
+        if let Ok(Some(description)) = self.kube_client.get_description(resource) {
+            out.push_str(&format!("{}\n\n", description.trim()));
         }
+
+        out.push_str(&format!("resources: {}\n", objs.len()));
+        if let (Some(oldest), Some(newest)) = (objs.first(), objs.last()) {
+            for (label, obj) in [("oldest", oldest), ("newest", newest)] {
+                let age = format_age(obj.creation_timestamp().map(|t| t.0));
+                out.push_str(&format!("{}: {} ({})\n", label, obj.name_any(), age));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Replicas of a workload for its `<name>.scale`, along with the state of the autoscaler
+    /// targeting it if there is one.
+    fn get_scale(&self, node: &KubeResourceNode) -> anyhow::Result<String> {
+        let obj = self.get_resource_object(node)?;
+        let namespace = node.namespace.clone().unwrap_or_default();
+
+        // Best effort, listing autoscalers may not be allowed or autoscaling/v2 not served.
+        let autoscalers = match self.kube_client.list_autoscalers(&namespace) {
+            Ok(autoscalers) => autoscalers,
+            Err(err) => {
+                warn!("Unable to list the autoscalers in {}: {:#}", namespace, err);
+                Vec::new()
+            }
+        };
+        let autoscaler = autoscalers.iter().find(|hpa| {
+            let target = &hpa.data["spec"]["scaleTargetRef"];
+            target["kind"] == node.kind.as_str() && target["name"] == obj.name_any().as_str()
+        });
+
+        Ok(format_scale(&obj, autoscaler))
+    }
+
+    /// Documentation of the fields of a kind for its `.explain`, `None` when its schema doesn't
+    /// document anything, as is the case for some CRDs.
+    fn get_explain(&self, api: &KubeApiResourceNode) -> anyhow::Result<Option<String>> {
+        let resource = self
+            .find_api_resource(api)
+            .ok_or(anyhow::Error::msg("Unknown api resource!"))?;
+        let document = self.kube_client.get_openapi_document(resource)?;
+
+        Ok(format_explain(resource, &document))
+    }
+
+    fn get_resource_table(&self, api: &KubeApiResourceNode) -> anyhow::Result<String> {
+        let resource = self
+            .find_api_resource(api)
+            .ok_or(anyhow::Error::msg("Unknown api resource!"))?;
+
+        let namespace = api
+            .namespace
+            .clone()
+            .ok_or(anyhow::Error::msg("Resource is not namespaced!"))?;
+
+        let objs = self
+            .kube_client
+            .list_resources(namespace.as_str(), resource)?;
+
+        let columns = match resource.kind.as_str() {
+            "Pod" => pod_columns(),
+            _ => self
+                .kube_client
+                .get_printer_columns(resource)
+                .unwrap_or_default(),
+        };
+
+        Ok(format_resource_table(&objs, &columns, self.wide))
+    }
+
+    fn get_namespaced_api_resources(&self) -> Vec<&ApiResource> {
+        self.api_resources
+            .iter()
+            .filter(|(_, c)| c.scope == Scope::Namespaced)
+            .map(|(a, _)| a)
+            .collect()
+    }
+
+    fn find_api_resource(&self, api: &KubeApiResourceNode) -> Option<&ApiResource> {
+        self.api_resources
+            .iter()
+            .find(|(a, _)| a.group == api.group && a.kind == api.kind && a.version == api.version)
+            .map(|(a, _)| a)
+    }
+
+    fn find_api_resource_by_kind(&self, kind: &str) -> anyhow::Result<&ApiResource> {
+        self.api_resources
+            .iter()
+            .find(|(a, _)| a.kind == kind)
+            .map(|(a, _)| a)
+            .ok_or(anyhow::Error::msg("Unknown api resource!"))
+    }
+
+    /// The resource quotas and limit ranges of a namespace.
+    fn list_quotas(
+        &self,
+        namespace: &str,
+    ) -> anyhow::Result<(Vec<DynamicObject>, Vec<DynamicObject>)> {
+        let quotas = self
+            .kube_client
+            .list_resources(namespace, self.find_api_resource_by_kind("ResourceQuota")?)?;
+        let limit_ranges = self
+            .kube_client
+            .list_resources(namespace, self.find_api_resource_by_kind("LimitRange")?)?;
+        Ok((quotas, limit_ranges))
+    }
+
+    fn get_resource_object(&self, node: &KubeResourceNode) -> anyhow::Result<DynamicObject> {
+        if node.kind == "Namespace" {
+            return self
+                .kube_client
+                .list_namespaces()?
+                .into_iter()
+                .find(|o| KubeResourceNode::identity(o, &node.kind) == node.uuid)
+                .ok_or(anyhow::Error::msg("Resource not found!"));
+        }
+
+        let resource = self.find_api_resource_by_kind(&node.kind)?;
+
+        let namespace = node
+            .namespace
+            .clone()
+            .ok_or(anyhow::Error::msg("Resource is not namespaced!"))?;
+
+        if self.lazy {
+            return self
+                .kube_client
+                .get_resource(namespace.as_str(), resource, node.name.as_str())?
+                .filter(|o| KubeResourceNode::identity(o, &node.kind) == node.uuid)
+                .ok_or(anyhow::Error::msg("Resource not found!"));
+        }
+
+        if node.qualified {
+            let informed = self
+                .kube_client
+                .list_all_resources(resource)
+                .and_then(Result::ok)
+                .and_then(|objs| {
+                    objs.into_iter()
+                        .find(|o| KubeResourceNode::identity(o, &node.kind) == node.uuid)
+                });
+            if let Some(obj) = informed {
+                return Ok(obj);
+            }
+        }
+
+        self.kube_client
+            .find_resource(namespace.as_str(), resource, node.name.as_str())?
+            .filter(|o| KubeResourceNode::identity(o, &node.kind) == node.uuid)
+            .ok_or(io::Error::new(io::ErrorKind::NotFound, "Resource not found!").into())
+    }
+
+    pub fn list_files_two(
+        &mut self,
+        inode: u64,
+    ) -> anyhow::Result<Option<Vec<(String, FileAttr)>>> {
+        if !self.sync_leafs_for_inode(inode)? {
+            return Ok(None);
+        }
+
+        let result: Option<Vec<(String, FileAttr)>> = self
+            .arena_two
+            .get_children(&NodeId::new(inode))
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .filter(|n| !is_manifest_view(&n.payload))
+                    .map(|n| (self.get_file_name(&n.payload), n))
+                    .filter(|(name, n)| {
+                        let valid = is_valid_file_name(name, &n.payload);
+                        if !valid {
+                            warn!(
+                                "Skipping {:?} in {}, it's not a valid file name",
+                                name, inode
+                            );
+                        }
+                        valid
+                    })
+                    .map(|(name, n)| (name, self.map_kube_file_to_attr(n)))
+                    .collect()
+            });
+
+        trace!(
+            "Files for {} ({}) are {:?}",
+            inode,
+            self.get_path(&NodeId::new(inode)).unwrap_or_default(),
+            result
+        );
+        Ok(result)
+    }
+
+    /// Human readable path of a node relative to the mount point, e.g. `default/default/pods/foo.yml`.
+    fn get_path(&self, id: &NodeId) -> Option<String> {
+        let names: Vec<String> = self
+            .arena_two
+            .path_to_root(id)?
+            .iter()
+            .rev()
+            .filter_map(|id| self.arena_two.get(id))
+            .map(|n| self.get_file_name(&n.payload))
+            .collect();
+
+        Some(names.join("/"))
+    }
+
+    /// Lists every inode as `<inode> -> <path> (<node type>)`, up to `MAX_LISTED_INODES` of them.
+    fn format_inodes(&self) -> String {
+        let mut inodes: Vec<u64> = self.arena_two.iter().map(|n| n.id.clone().into()).collect();
+        inodes.sort();
+
+        let mut lines = String::new();
+        for inode in inodes.iter().take(MAX_LISTED_INODES) {
+            let id = NodeId::new(*inode);
+            let node_type = self
+                .arena_two
+                .get(&id)
+                .map(|n| format!("{:?}", n.payload))
+                .unwrap_or_default();
+            // The variant name is all of the debug output up to its fields.
+            let node_type = node_type.split(['(', ' ']).next().unwrap_or_default();
+
+            lines.push_str(&format!(
+                "{} -> {} ({})\n",
+                inode,
+                self.get_path(&id).unwrap_or_default(),
+                node_type
+            ));
+        }
+
+        if inodes.len() > MAX_LISTED_INODES {
+            lines.push_str(&format!("... {} more\n", inodes.len() - MAX_LISTED_INODES));
+        }
+
+        lines
+    }
+
+    /// Describes the mount as json: the context, the namespaces, every kind and the files each
+    /// resource of that kind comes with. Paths are relative to the directory of the kind, with
+    /// `<name>` and friends as placeholders.
+    fn format_index(&self) -> anyhow::Result<String> {
+        let ext = match self.manifest_type {
+            KubeManifestType::Json => "json",
+            KubeManifestType::Yaml => self.extension.as_str(),
+        };
+
+        let namespaces: Vec<String> = self
+            .list_visible_namespaces()?
+            .iter()
+            .map(|n| n.name_any())
+            .collect();
+
+        let kinds: Vec<serde_json::Value> =
+            self.api_resources
+                .iter()
+                .map(|(resource, capabilities)| {
+                    let mut files = vec![String::from(".table")];
+                    if self.readme {
+                        files.push(String::from("README"));
+                    }
+                    if self.explain {
+                        files.push(String::from(".explain"));
+                    }
+                    if !self.hide_manifest {
+                        files.extend([format!("<name>.{}", ext), String::from("<name>.diff")]);
+                    }
+                    files.extend([
+                        format!("<name>.{}?<path>", ext),
+                        format!("<name>@<resourceVersion>.{}", ext),
+                        format!("<name>.owned.<manager>.{}", ext),
+                    ]);
+                    if SCALABLE_KINDS.contains(&resource.kind.as_str()) {
+                        files.push(String::from("<name>.scale"));
+                    }
+
+                    match resource.kind.as_str() {
+                        "Pod" => {
+                            files.push(String::from("<name>/events"));
+                            files.extend(
+                                ["summary", "logs", "logs.ts"]
+                                    .iter()
+                                    .map(|f| format!("<name>/{}", f)),
+                            );
+                            if self.metrics_available {
+                                files.push(String::from("<name>/metrics"));
+                            }
+                            files.extend(
+                                ["logs", "logs.previous", "image", "status"]
+                                    .iter()
+                                    .map(|f| format!("<name>/containers/<container>/{}", f)),
+                            );
+                        }
+                        "ConfigMap" | "Secret" => files
+                            .extend([String::from("<name>/<key>"), String::from("<name>/events")]),
+                        _ => {}
+                    }
+
+                    json!({
+                        "kind": resource.kind,
+                        "apiVersion": resource.api_version,
+                        "plural": resource.plural,
+                        "namespaced": capabilities.scope == Scope::Namespaced,
+                        "verbs": capabilities.operations,
+                        "writable": self.is_kind_writable(&resource.kind),
+                        "directory": match self.layout {
+                            Layout::Kind => format!("{}/<namespace>", resource.plural),
+                            _ if self.group_by_apigroup && !resource.group.is_empty() => {
+                                format!("<namespace>/{}/{}", resource.group, resource.plural)
+                            }
+                            _ => format!("<namespace>/{}", resource.plural),
+                        },
+                        "files": files,
+                    })
+                })
+                .collect();
+
+        let mut files = vec![
+            ".errors",
+            ".resources",
+            ".version",
+            ".connection",
+            ".kubefs/flush",
+            ".kubefs/inodes",
+            ".kubefs/index.json",
+        ];
+        if self.layout == Layout::Context {
+            files.extend(["cluster_info", ".apiservices"]);
+            if self.metrics_available {
+                files.push(".metrics");
+            }
+        }
+        if self.watch {
+            files.push("~recent");
+        }
+        let get_files = format!("~get/<plural>/<namespace>.<name>.{}", ext);
+        if self.layout != Layout::Kind && !self.hide_manifest {
+            files.push(&get_files);
+        }
+        if self.read_write {
+            files.push(".apply/last-result");
+        }
+
+        let index = json!({
+            "version": VERSION,
+            "context": self.kube_client.current_context(),
+            "layout": format!("{:?}", self.layout).to_lowercase(),
+            "format": ext,
+            "readWrite": self.read_write,
+            "namespaces": namespaces,
+            "files": files,
+            "kinds": kinds,
+        });
+
+        Ok(format!("{}\n", serde_json::to_string_pretty(&index)?))
+    }
+
+    /// Names of the extended attributes of an inode, only api-resource directories have any.
+    pub fn list_xattrs(&self, inode: u64) -> Vec<&'static str> {
+        match self.find_xattr_resource(inode) {
+            Some(_) => XATTRS.to_vec(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Discovery metadata of the kind behind an api-resource directory, `getfattr -d pods`.
+    pub fn get_xattr(&self, inode: u64, name: &str) -> Option<String> {
+        let (resource, capabilities) = self.find_xattr_resource(inode)?;
+
+        match name {
+            "user.kubefs.group" => Some(resource.group.clone()),
+            "user.kubefs.version" => Some(resource.version.clone()),
+            "user.kubefs.scope" => Some(match capabilities.scope {
+                Scope::Namespaced => String::from("Namespaced"),
+                Scope::Cluster => String::from("Cluster"),
+            }),
+            "user.kubefs.shortnames" => match self.kube_client.get_short_names(resource) {
+                Ok(short_names) => Some(short_names.join(",")),
+                Err(err) => {
+                    warn!(
+                        "Unable to look up the short names of {}: {:#}",
+                        resource.kind, err
+                    );
+                    Some(String::new())
+                }
+            },
+            "user.kubefs.verbs" => Some(capabilities.operations.join(",")),
+            _ => None,
+        }
+    }
+
+    fn find_xattr_resource(&self, inode: u64) -> Option<&(ApiResource, ApiCapabilities)> {
+        let api = match self.arena_two.get(&NodeId::new(inode)).map(|n| &n.payload) {
+            Some(KubeFileNode::ApiResourceDirectory(api))
+            | Some(KubeFileNode::GetKindDirectory(api)) => api,
+            _ => return None,
+        };
+
+        self.api_resources
+            .iter()
+            .find(|(a, _)| a.group == api.group && a.kind == api.kind && a.version == api.version)
+    }
+
+    pub fn read_link(&self, inode: u64) -> Option<String> {
+        match self.arena_two.get(&NodeId::new(inode)).map(|n| &n.payload) {
+            Some(KubeFileNode::RecentLink(change)) => {
+                Some(self.get_link_target(&NodeId::new(inode), change))
+            }
+            Some(KubeFileNode::CurrentLink(r)) => Some(self.get_current_target(r)),
+            Some(KubeFileNode::OwnerLink(r)) => self.get_owner_target(&NodeId::new(inode), r),
+            _ => None,
+        }
+    }
+
+    /// Path of the manifest file of a changed resource, relative to the directory of the link
+    /// `id`.
+    fn get_link_target(&self, id: &NodeId, change: &ResourceChange) -> String {
+        self.get_manifest_target(
+            id,
+            &change.namespace,
+            &change.group,
+            &change.plural,
+            &change.name,
+        )
+    }
+
+    /// Path of the manifest of the resource owning `resource`, relative to the directory of the
+    /// link `id`. `None` when nothing in the mount owns it.
+    fn get_owner_target(&self, id: &NodeId, resource: &KubeResourceNode) -> Option<String> {
+        let (group, plural, name) = self.get_owner(resource)?;
+        let namespace = resource.namespace.as_deref()?;
+        Some(self.get_manifest_target(id, namespace, &group, &plural, &name))
+    }
+
+    /// The controller of a resource, or its first owner when none of them controls it, as the
+    /// group, plural and name of it. Owners that aren't namespaced have no manifest next to the
+    /// resource and are left out.
+    fn get_owner(&self, resource: &KubeResourceNode) -> Option<(String, String, String)> {
+        let obj = self.get_resource_object(resource).ok()?;
+        let owners = obj.metadata.owner_references.unwrap_or_default();
+        let owner = owners
+            .iter()
+            .find(|o| o.controller == Some(true))
+            .or(owners.first())?;
+        let group = owner.api_version.split_once('/').map_or("", |(g, _)| g);
+
+        self.api_resources
+            .iter()
+            .find(|(a, c)| a.group == group && a.kind == owner.kind && c.scope == Scope::Namespaced)
+            .map(|(a, _)| (a.group.clone(), a.plural.clone(), owner.name.clone()))
+    }
+
+    /// Path of the manifest of a resource, relative to the directory of the link `id`. Namespaces
+    /// hang off the context, so the link climbs up to it first.
+    fn get_manifest_target(
+        &self,
+        id: &NodeId,
+        namespace: &str,
+        group: &str,
+        plural: &str,
+        name: &str,
+    ) -> String {
+        let context = self
+            .arena_two
+            .path_to_root(id)
+            .unwrap_or_default()
+            .into_iter()
+            .skip(1)
+            .find(|a| {
+                matches!(
+                    self.arena_two.get(a).map(|n| &n.payload),
+                    Some(KubeFileNode::Context(_))
+                )
+            });
+        let base = context
+            .and_then(|c| self.arena_two.depth(&c))
+            .unwrap_or_default();
+        let depth = self.arena_two.depth(id).unwrap_or_default();
+        let up = "../".repeat(depth.saturating_sub(base + 1));
+
+        let file = KubeFileNode::ResourceFile(KubeResourceNode {
+            namespace: Some(namespace.to_string()),
+            uuid: String::new(),
+            name: name.to_string(),
+            kind: String::new(),
+            qualified: false,
+        });
+        let file = self.get_file_name(&file);
+
+        match self.layout {
+            Layout::Kind => format!("{}{}/{}/{}", up, plural, namespace, file),
+            _ if self.group_by_apigroup && !group.is_empty() => {
+                format!("{}{}/{}/{}/{}", up, namespace, group, plural, file)
+            }
+            _ => format!("{}{}/{}/{}", up, namespace, plural, file),
+        }
+    }
+
+    /// Path of the manifest of a resource, relative to the directory of the resource.
+    fn get_current_target(&self, resource: &KubeResourceNode) -> String {
+        let file = KubeFileNode::ResourceFile(KubeResourceNode {
+            qualified: false,
+            ..resource.clone()
+        });
+        format!("../{}", self.get_file_name(&file))
+    }
+
+    fn get_file_name(&self, node: &KubeFileNode) -> String {
+        match self.manifest_type {
+            KubeManifestType::Json => node.get_file_name("json"),
+            KubeManifestType::Yaml => node.get_file_name(self.extension.as_str()),
+        }
+    }
+
+    fn map_kube_file_to_attr(&self, node: &Node<KubeFileNode>) -> FileAttr {
+        match &node.payload {
+            KubeFileNode::ApiResourceDirectory(api) | KubeFileNode::NamespaceDirectory(api)
+                if self.lazy && api.namespace.is_some() =>
+            {
+                FileAttr {
+                    ino: node.id.clone().into(),
+                    // Holds the number of resources, so the directory shows up as populated
+                    // without listing it.
+                    size: self
+                        .find_api_resource(api)
+                        .and_then(|r| {
+                            self.kube_client
+                                .count_resources(api.namespace.clone().unwrap().as_str(), r)
+                                .ok()
+                        })
+                        .unwrap_or(0),
+                    blocks: 0,
+                    atime: self.startup,
+                    mtime: self.startup,
+                    ctime: self.startup,
+                    crtime: self.startup,
+                    kind: FileType::Directory,
+                    perm: 0o755,
+                    nlink: 1,
+                    uid: 1000,
+                    gid: 1000,
+                    rdev: 0,
+                    blksize: self.blksize,
+                    flags: 0,
+                }
+            }
+            KubeFileNode::Virtual(_)
+            | KubeFileNode::Context(_)
+            | KubeFileNode::ApiResourceDirectory(_)
+            | KubeFileNode::NamespaceDirectory(_)
+            | KubeFileNode::RecentDirectory
+            | KubeFileNode::ApiGroupDirectory(..)
+            | KubeFileNode::GetDirectory
+            | KubeFileNode::GetKindDirectory(_)
+            | KubeFileNode::LabelsDirectory(_)
+            | KubeFileNode::LabelKeyDirectory(..)
+            | KubeFileNode::LabelValueDirectory(..)
+            | KubeFileNode::HealthDirectory(..)
+            | KubeFileNode::ControlDirectory
+            | KubeFileNode::ApplyDirectory
+            | KubeFileNode::ContainersDirectory(_)
+            | KubeFileNode::ContainerDirectory(..)
+            | KubeFileNode::ResourceDirectory(_) => FileAttr {
+                ino: node.id.clone().into(),
+                size: 0,
+                blocks: 0,
+                atime: self.startup,
+                mtime: self.startup,
+                ctime: self.startup,
+                crtime: self.startup,
+                kind: FileType::Directory,
+                perm: 0o755,
+                nlink: 1,
+                uid: 1000,
+                gid: 1000,
+                rdev: 0,
+                blksize: self.blksize,
+                flags: 0,
+            },
+            KubeFileNode::RecentLink(change) => FileAttr {
+                ino: node.id.clone().into(),
+                size: self.get_link_target(&node.id, change).len() as u64,
+                blocks: 0,
+                atime: change.time,
+                mtime: change.time,
+                ctime: change.time,
+                crtime: change.time,
+                kind: FileType::Symlink,
+                perm: 0o777,
+                nlink: 1,
+                uid: 1000,
+                gid: 1000,
+                rdev: 0,
+                blksize: self.blksize,
+                flags: 0,
+            },
+            KubeFileNode::CurrentLink(resource) => FileAttr {
+                ino: node.id.clone().into(),
+                size: self.get_current_target(resource).len() as u64,
+                blocks: 0,
+                atime: self.startup,
+                mtime: self.startup,
+                ctime: self.startup,
+                crtime: self.startup,
+                kind: FileType::Symlink,
+                perm: 0o777,
+                nlink: 1,
+                uid: 1000,
+                gid: 1000,
+                rdev: 0,
+                blksize: self.blksize,
+                flags: 0,
+            },
+            KubeFileNode::OwnerLink(resource) => FileAttr {
+                ino: node.id.clone().into(),
+                size: self
+                    .get_owner_target(&node.id, resource)
+                    .unwrap_or_default()
+                    .len() as u64,
+                blocks: 0,
+                atime: self.startup,
+                mtime: self.startup,
+                ctime: self.startup,
+                crtime: self.startup,
+                kind: FileType::Symlink,
+                perm: 0o777,
+                nlink: 1,
+                uid: 1000,
+                gid: 1000,
+                rdev: 0,
+                blksize: self.blksize,
+                flags: 0,
+            },
+            KubeFileNode::ResourceFile(file) => FileAttr {
+                ino: node.id.clone().into(),
+                size: self
+                    .get_manifest_file(file)
+                    .map(|m| m.get_size())
+                    .unwrap_or(0),
+                blocks: 0,
+                atime: self.startup,
+                mtime: self.startup,
+                ctime: self.startup,
+                crtime: self.startup,
+                kind: FileType::RegularFile,
+                perm: 0o655,
+                nlink: 1,
+                uid: 1000,
+                gid: 1000,
+                rdev: 0,
+                blksize: self.blksize,
+                flags: 0,
+            },
+            KubeFileNode::DataKeyFile(..)
+            | KubeFileNode::ProjectionFile(..)
+            | KubeFileNode::OwnedFieldsFile(..)
+            | KubeFileNode::MetaFile
+            | KubeFileNode::VersionedResourceFile(..)
+            | KubeFileNode::NewResourceFile(..)
+            | KubeFileNode::ConfirmDeleteFile(_)
+            | KubeFileNode::ConnectionFile
+            | KubeFileNode::ReadmeFile(_)
+            | KubeFileNode::InodesFile
+            | KubeFileNode::IndexFile
+            | KubeFileNode::ContainerImageFile(..)
+            | KubeFileNode::ContainerStatusFile(..)
+            | KubeFileNode::DiffFile(_)
+            | KubeFileNode::ApplyFile(_)
+            | KubeFileNode::ApplyResultFile
+            | KubeFileNode::PatchFile(_)
+            | KubeFileNode::PatchResultFile(_)
+            | KubeFileNode::FlushFile => FileAttr {
+                ino: node.id.clone().into(),
+                size: self
+                    .read_file(node.id.clone().into())
+                    .map(|v| v.len() as u64)
+                    .unwrap_or(0),
+                blocks: 0,
+                atime: self.startup,
+                mtime: self.startup,
+                ctime: self.startup,
+                crtime: self.startup,
+                kind: FileType::RegularFile,
+                perm: 0o655,
+                nlink: 1,
+                uid: 1000,
+                gid: 1000,
+                rdev: 0,
+                blksize: self.blksize,
+                flags: 0,
+            },
+            KubeFileNode::ClusterInfoFile
+            | KubeFileNode::ErrorsFile
+            | KubeFileNode::ResourcesFile
+            | KubeFileNode::PodSummaryFile(_)
+            | KubeFileNode::QuotaFile(_)
+            | KubeFileNode::EventsFile(_)
+            | KubeFileNode::NodeMetricsFile
+            | KubeFileNode::ApiServicesFile
+            | KubeFileNode::PodMetricsFile(_)
+            | KubeFileNode::ScaleFile(_)
+            | KubeFileNode::ExplainFile(_)
+            | KubeFileNode::VersionFile
+            | KubeFileNode::LogFile(..)
+            | KubeFileNode::ContainerLogFile(..)
+            | KubeFileNode::TableFile(_) => FileAttr {
+                ino: node.id.clone().into(),
+                size: 10000,
+                blocks: 0,
+                atime: self.startup,
+                mtime: self.startup,
+                ctime: self.startup,
+                crtime: self.startup,
+                kind: FileType::RegularFile,
+                perm: 0o655,
+                nlink: 1,
+                uid: 10000,
+                gid: 1000,
+                rdev: 0,
+                blksize: self.blksize,
+                flags: 0,
+            },
+        }
+    }
+
+    /// The children a node should have according to the cluster.
+    fn get_leafs_for_node(&self, node: &Node<KubeFileNode>) -> anyhow::Result<Vec<KubeFileNode>> {
+        let mut leafs = match &node.payload {
+            KubeFileNode::Context(_) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+                items.push(KubeFileNode::ErrorsFile);
+                items.push(KubeFileNode::ResourcesFile);
+                items.push(KubeFileNode::VersionFile);
+                items.push(KubeFileNode::ConnectionFile);
+                items.push(KubeFileNode::ControlDirectory);
+
+                if self.read_write {
+                    items.push(KubeFileNode::ApplyDirectory);
+                }
+
+                if self.layout == Layout::Kind {
+                    for api in self.get_namespaced_api_resources() {
+                        let n = KubeApiResourceNode {
+                            namespace: None,
+                            group: api.group.clone(),
+                            kind: api.kind.clone(),
+                            version: api.version.clone(),
+                            plural: api.plural.clone(),
+                        };
+                        items.push(KubeFileNode::ApiResourceDirectory(n));
+                    }
+                    return Ok(items);
+                }
+
+                if self.watch {
+                    items.push(KubeFileNode::RecentDirectory);
+                }
+                // Holds nothing but manifests.
+                if !self.hide_manifest {
+                    items.push(KubeFileNode::GetDirectory);
+                }
+
+                if self.layout == Layout::Context {
+                    items.push(KubeFileNode::ClusterInfoFile);
+                    items.push(KubeFileNode::ApiServicesFile);
+
+                    if self.metrics_available {
+                        items.push(KubeFileNode::NodeMetricsFile);
+                    }
+                }
+
+                let namespaces = self.list_visible_namespaces()?;
+
+                for namespace in namespaces {
+                    let n = KubeResourceNode::from(&namespace, "Namespace");
+                    items.push(KubeFileNode::ResourceDirectory(n.clone()));
+                    if self.layout == Layout::Context {
+                        items.push(KubeFileNode::ResourceFile(n));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::ResourceDirectory(dir) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                debug!("Rendering Resource Directory {:?}", dir);
+
+                match dir.kind.as_str() {
+                    "Namespace" => {
+                        let mut groups = Vec::new();
+                        for api in self.get_namespaced_api_resources() {
+                            if self.group_by_apigroup && !api.group.is_empty() {
+                                if !groups.contains(&api.group) {
+                                    groups.push(api.group.clone());
+                                }
+                                continue;
+                            }
+
+                            if let Some(n) = self.namespaced_api_resource_node(&dir.name, api) {
+                                items.push(KubeFileNode::ApiResourceDirectory(n));
+                            }
+                        }
+
+                        for group in groups {
+                            items.push(KubeFileNode::ApiGroupDirectory(dir.name.clone(), group));
+                        }
+
+                        if let Ok((quotas, limit_ranges)) = self.list_quotas(&dir.name) {
+                            if !quotas.is_empty() || !limit_ranges.is_empty() {
+                                items.push(KubeFileNode::QuotaFile(dir.clone()));
+                            }
+                        }
+                    }
+                    "Pod" => {
+                        items.push(KubeFileNode::PodSummaryFile(dir.clone()));
+                        items.push(KubeFileNode::LogFile(dir.clone(), false));
+                        items.push(KubeFileNode::LogFile(dir.clone(), true));
+                        items.push(KubeFileNode::ContainersDirectory(dir.clone()));
+
+                        if self.metrics_available {
+                            items.push(KubeFileNode::PodMetricsFile(dir.clone()));
+                        }
+                    }
+                    "ConfigMap" | "Secret" => {
+                        let obj = self.get_resource_object(dir);
+                        let keys: Vec<String> = obj
+                            .as_ref()
+                            .ok()
+                            .map(|o| {
+                                ["data", "binaryData"]
+                                    .iter()
+                                    .filter_map(|field| o.data[field].as_object())
+                                    .flat_map(|data| data.keys().cloned())
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+
+                        for key in keys {
+                            items.push(KubeFileNode::DataKeyFile(dir.clone(), key));
+                        }
+                    }
+                    _ => {}
+                }
+
+                if dir.kind != "Namespace" {
+                    items.push(KubeFileNode::EventsFile(dir.clone()));
+
+                    if self.is_kind_writable(&dir.kind) {
+                        items.push(KubeFileNode::PatchFile(dir.clone()));
+                        items.push(KubeFileNode::PatchResultFile(dir.clone()));
+                    }
+
+                    // A data key named `current` keeps its name, the link makes way for it.
+                    let link = KubeFileNode::CurrentLink(dir.clone());
+                    if !items.iter().any(|i| self.get_file_name(i) == CURRENT_LINK) {
+                        items.push(link);
+                    }
+
+                    let owned = self.get_owner(dir).is_some();
+                    if owned && !items.iter().any(|i| self.get_file_name(i) == OWNER_LINK) {
+                        items.push(KubeFileNode::OwnerLink(dir.clone()));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::ApiResourceDirectory(api) if api.namespace.is_none() => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                let namespaces = self.list_visible_namespaces()?;
+
+                for namespace in namespaces {
+                    let mut n = api.clone();
+                    n.namespace = Some(namespace.name_any());
+                    items.push(KubeFileNode::NamespaceDirectory(n));
+                }
+
+                items
+            }
+            KubeFileNode::ApiResourceDirectory(api) | KubeFileNode::NamespaceDirectory(api) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                items.push(KubeFileNode::TableFile(api.clone()));
+                if self.readme {
+                    items.push(KubeFileNode::ReadmeFile(api.clone()));
+                }
+                // Rendered on read only, the file is empty when the cluster has no schema for the
+                // kind or the schema has no documentation.
+                if self.explain {
+                    items.push(KubeFileNode::ExplainFile(api.clone()));
+                }
+
+                let objs = self.list_api_resource_objects(api)?;
+
+                let directories = matches!(api.kind.as_str(), "Pod" | "ConfigMap" | "Secret");
+                for obj in &objs {
+                    let n = KubeResourceNode::from(obj, &api.kind);
+                    if directories {
+                        items.push(KubeFileNode::ResourceDirectory(n.clone()));
+                    }
+                    items.push(KubeFileNode::DiffFile(n.clone()));
+                    if SCALABLE_KINDS.contains(&api.kind.as_str()) {
+                        items.push(KubeFileNode::ScaleFile(n.clone()));
+                    }
+                    items.push(KubeFileNode::ResourceFile(n));
+                }
+
+                // A resource named after one of the overviews gets its directory, the overview
+                // makes way for it.
+                let mut overviews = vec![KubeFileNode::LabelsDirectory(api.clone())];
+                if self.health_split && HEALTH_KINDS.contains(&api.kind.as_str()) {
+                    overviews.push(KubeFileNode::HealthDirectory(api.clone(), true));
+                    overviews.push(KubeFileNode::HealthDirectory(api.clone(), false));
+                }
+                for overview in overviews {
+                    let name = overview.get_file_name(&self.extension);
+                    if !directories || !objs.iter().any(|o| o.name_any() == name) {
+                        items.push(overview);
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::HealthDirectory(api, ready) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                for obj in self.list_api_resource_objects(api)? {
+                    if is_ready(&obj, &api.kind) == *ready {
+                        let n = KubeResourceNode::from(&obj, &api.kind);
+                        items.push(KubeFileNode::ResourceFile(n));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::LabelsDirectory(api) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                let keys: BTreeSet<String> = self
+                    .list_api_resource_objects(api)?
+                    .iter()
+                    .flat_map(|o| o.labels().keys().cloned())
+                    .collect();
+
+                for key in keys {
+                    items.push(KubeFileNode::LabelKeyDirectory(api.clone(), key));
+                }
+
+                items
+            }
+            KubeFileNode::LabelKeyDirectory(api, key) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                // Label values can be empty, which can't be the name of a directory.
+                let values: BTreeSet<String> = self
+                    .list_api_resource_objects(api)?
+                    .iter()
+                    .filter_map(|o| o.labels().get(key).cloned())
+                    .filter(|v| !v.is_empty())
+                    .collect();
+
+                for value in values {
+                    items.push(KubeFileNode::LabelValueDirectory(
+                        api.clone(),
+                        key.clone(),
+                        value,
+                    ));
+                }
+
+                items
+            }
+            KubeFileNode::LabelValueDirectory(api, key, value) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                for obj in self.list_api_resource_objects(api)? {
+                    if obj.labels().get(key) == Some(value) {
+                        let n = KubeResourceNode::from(&obj, &api.kind);
+                        items.push(KubeFileNode::ResourceFile(n));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::RecentDirectory => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                let mut seen = HashSet::new();
+                for change in self.kube_client.recent_changes() {
+                    let name = (
+                        change.namespace.clone(),
+                        change.plural.clone(),
+                        change.name.clone(),
+                    );
+                    if seen.insert(name) {
+                        items.push(KubeFileNode::RecentLink(change));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::ApiGroupDirectory(namespace, group) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                for api in self.get_namespaced_api_resources() {
+                    if &api.group != group {
+                        continue;
+                    }
+                    if let Some(n) = self.namespaced_api_resource_node(namespace, api) {
+                        items.push(KubeFileNode::ApiResourceDirectory(n));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::GetDirectory => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                for api in self.get_namespaced_api_resources() {
+                    items.push(KubeFileNode::GetKindDirectory(KubeApiResourceNode {
+                        namespace: None,
+                        group: api.group.clone(),
+                        kind: api.kind.clone(),
+                        version: api.version.clone(),
+                        plural: api.plural.clone(),
+                    }));
+                }
+
+                items
+            }
+            KubeFileNode::GetKindDirectory(api) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                let resource = self
+                    .find_api_resource(api)
+                    .ok_or(anyhow::Error::msg("Unknown api resource!"))?;
+
+                // Falls back to listing per namespace when we aren't allowed to list across the
+                // cluster.
+                match self.kube_client.list_all_resources(resource) {
+                    Some(Ok(objs)) => {
+                        for obj in &objs {
+                            items.push(KubeFileNode::ResourceFile(KubeResourceNode::qualified(
+                                obj, &api.kind,
+                            )));
+                        }
+                        return Ok(items);
+                    }
+                    Some(Err(err)) => {
+                        warn!("Unable to list {} in every namespace: {}", api.kind, err)
+                    }
+                    None => {}
+                }
+
+                // Listing per namespace rather than across the cluster shares the cached listings
+                // with the namespace directories, and skips namespaces we can't list in.
+                for namespace in self.kube_client.list_namespaces()? {
+                    let objs = self
+                        .kube_client
+                        .list_resources(namespace.name_any().as_str(), resource)
+                        .unwrap_or_default();
+
+                    for obj in &objs {
+                        items.push(KubeFileNode::ResourceFile(KubeResourceNode::qualified(
+                            obj, &api.kind,
+                        )));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::ControlDirectory => vec![
+                KubeFileNode::Virtual(String::from(".")),
+                KubeFileNode::Virtual(String::from("..")),
+                KubeFileNode::FlushFile,
+                KubeFileNode::InodesFile,
+                KubeFileNode::IndexFile,
+            ],
+            KubeFileNode::ContainersDirectory(pod) => {
+                let mut items = Vec::new();
+                items.push(KubeFileNode::Virtual(String::from(".")));
+                items.push(KubeFileNode::Virtual(String::from("..")));
+
+                if let Ok(obj) = self.get_resource_object(pod) {
+                    for container in container_names(&obj) {
+                        items.push(KubeFileNode::ContainerDirectory(pod.clone(), container));
+                    }
+                }
+
+                items
+            }
+            KubeFileNode::ContainerDirectory(pod, container) => vec![
+                KubeFileNode::Virtual(String::from(".")),
+                KubeFileNode::Virtual(String::from("..")),
+                KubeFileNode::ContainerLogFile(pod.clone(), container.clone(), false),
+                KubeFileNode::ContainerLogFile(pod.clone(), container.clone(), true),
+                KubeFileNode::ContainerImageFile(pod.clone(), container.clone()),
+                KubeFileNode::ContainerStatusFile(pod.clone(), container.clone()),
+            ],
+            KubeFileNode::ApplyDirectory => vec![
+                KubeFileNode::Virtual(String::from(".")),
+                KubeFileNode::Virtual(String::from("..")),
+                KubeFileNode::ApplyResultFile,
+            ],
+            _ => Vec::new(),
+        };
+
+        // The links point at manifests and the diffs compare against them, with those gone they
+        // would dangle.
+        if self.hide_manifest {
+            leafs.retain(|l| {
+                !matches!(
+                    l,
+                    KubeFileNode::ResourceFile(_)
+                        | KubeFileNode::DiffFile(_)
+                        | KubeFileNode::CurrentLink(_)
+                        | KubeFileNode::OwnerLink(_)
+                        | KubeFileNode::RecentLink(_)
+                )
+            });
+        }
+
+        Ok(leafs)
+    }
+
+    /// The resources of a kind in the namespace of `api`, from the cached listing.
+    fn list_api_resource_objects(
+        &self,
+        api: &KubeApiResourceNode,
+    ) -> anyhow::Result<Vec<DynamicObject>> {
+        let resource = self
+            .find_api_resource(api)
+            .ok_or(anyhow::Error::msg("Unknown api resource!"))?;
+        let namespace = api
+            .namespace
+            .clone()
+            .ok_or(anyhow::Error::msg("Resource is not namespaced!"))?;
+
+        self.kube_client.list_resources(&namespace, resource)
+    }
+
+    /// The directory of a kind in a namespace, None when it's left out by `hide_empty`.
+    fn namespaced_api_resource_node(
+        &self,
+        namespace: &str,
+        api: &ApiResource,
+    ) -> Option<KubeApiResourceNode> {
+        if self.hide_empty
+            && self
+                .kube_client
+                .count_resources(namespace, api)
+                .is_ok_and(|count| count == 0)
+        {
+            return None;
+        }
+
+        Some(KubeApiResourceNode {
+            namespace: Some(namespace.into()),
+            group: api.group.clone(),
+            kind: api.kind.clone(),
+            version: api.version.clone(),
+            plural: api.plural.clone(),
+        })
+    }
+
+    /// The namespaces to show a directory for, leaving out the ones of the cluster itself with
+    /// `hide_system_namespaces` and the ones in `hidden_namespaces`.
+    fn list_visible_namespaces(&self) -> anyhow::Result<Vec<DynamicObject>> {
+        let mut namespaces = self.kube_client.list_namespaces()?;
+        if self.hide_system_namespaces {
+            namespaces.retain(|n| !is_system_namespace(n));
+        }
+        namespaces.retain(|n| !self.hidden_namespaces.contains(&n.name_any()));
+        Ok(namespaces)
+    }
+
+    /// Contents of the `.meta` file of a directory.
+    fn format_meta(&self, directory: &NodeId) -> String {
+        let state = self
+            .sync_states
+            .get(&directory.clone().into())
+            .cloned()
+            .unwrap_or_default();
+
+        let cached = match self.arena_two.get(directory).map(|n| &n.payload) {
+            Some(KubeFileNode::ApiResourceDirectory(api))
+            | Some(KubeFileNode::NamespaceDirectory(api)) => api
+                .namespace
+                .as_ref()
+                .zip(self.find_api_resource_by_kind(&api.kind).ok())
+                .and_then(|(namespace, resource)| {
+                    self.kube_client.cached_count(namespace, resource)
+                })
+                .map(|count| format!("{} objects", count)),
+            _ => None,
+        };
+
+        let cache_ttl = match (self.kube_client.is_caching(), self.kube_client.cache_ttl()) {
+            (false, _) => String::from("disabled"),
+            (true, Some(ttl)) => format!("{:?}", ttl),
+            (true, None) => String::from("none"),
+        };
+
+        let rows = vec![
+            vec![
+                String::from("path"),
+                self.get_path(directory).unwrap_or_default(),
+            ],
+            vec![
+                String::from("synced"),
+                match state.synced {
+                    Some(time) => format!("{} ({} ago)", time.to_rfc3339(), format_age(Some(time))),
+                    None => String::from("never"),
+                },
+            ],
+            vec![String::from("cache ttl"), cache_ttl],
+            vec![
+                String::from("cached"),
+                cached.unwrap_or_else(|| String::from("-")),
+            ],
+            vec![
+                String::from("last error"),
+                state.error.unwrap_or_else(|| String::from("-")),
+            ],
+        ];
+
+        format_table(&rows)
+    }
+
+    /// Whether the namespace a directory belongs to is still around. Namespaces deleted while
+    /// mounted stick around in the tree otherwise, presenting a subtree that errors on access.
+    fn namespace_exists(&self, node: &KubeFileNode) -> bool {
+        let name = match node {
+            KubeFileNode::ResourceDirectory(dir) if dir.kind == "Namespace" => &dir.name,
+            KubeFileNode::NamespaceDirectory(api) => match &api.namespace {
+                Some(namespace) => namespace,
+                None => return true,
+            },
+            _ => return true,
+        };
+
+        match self.kube_client.list_namespaces() {
+            Ok(namespaces) => namespaces.iter().any(|n| &n.name_any() == name),
+            Err(_) => true,
+        }
+    }
+
+    /// Brings the children of a node in line with the cluster. Returns false when the node
+    /// itself no longer exists, in which case it gets removed from the tree. When the children
+    /// can't be fetched they are left as they were, unless the mount is `strict`.
+    fn sync_leafs_for_inode(&mut self, inode: u64) -> anyhow::Result<bool> {
+        let id = NodeId::new(inode);
+        debug!(
+            "syncing leafs for node {} ({})",
+            inode,
+            self.get_path(&id).unwrap_or_default()
+        );
+        let node = self.arena_two.get(&id);
+
+        if node.is_none() {
+            return Ok(false);
+        }
+
+        let node = node.unwrap();
+
+        if !self.namespace_exists(&node.payload) {
+            info!("Namespace of node {} is gone, removing it", inode);
+            if node.parent_id.is_some() {
+                let removed = self.arena_two.delete_node(id).unwrap_or_default();
+                self.forget_sync_states(removed);
+            }
+            return Ok(false);
+        }
+
+        let is_directory = node.payload.is_directory();
+        let leafs = self.get_leafs_for_node(node);
+        let state = self.sync_states.entry(inode).or_default();
+
+        let mut new_leaf = match leafs {
+            Ok(leafs) => {
+                state.synced = Some(Utc::now());
+                state.error = None;
+                leafs
+            }
+            Err(err) => {
+                state.error = Some(format!("{:#}", err));
+                if self.strict {
+                    return Err(err);
+                }
+                warn!("Unable to list {}: {:#}", inode, err);
+                return Ok(true);
+            }
+        };
+
+        if is_directory {
+            new_leaf.push(KubeFileNode::MetaFile);
+        }
+
+        // Manifests being created aren't in the cluster yet, so they are never in new_leaf. Nor
+        // are the views of hidden manifests, which hang off the directory.
+        let removed = self.arena_two.sync_children(&id, new_leaf, |n| {
+            matches!(
+                n,
+                KubeFileNode::NewResourceFile(..)
+                    | KubeFileNode::ApplyFile(_)
+                    | KubeFileNode::ConfirmDeleteFile(_)
+            ) || is_manifest_view(n)
+        });
+        self.forget_sync_states(removed.unwrap_or_default());
+
+        Ok(true)
+    }
+
+    /// Drops the sync states of nodes that got removed from the tree.
+    fn forget_sync_states(&mut self, removed: impl IntoIterator<Item = NodeId>) {
+        for id in removed {
+            self.sync_states.remove(&id.into());
+        }
+    }
+}
+
+/// The value of a key of a ConfigMap or Secret. The data of Secrets and the binaryData of
+/// ConfigMaps are base64 encoded, those are decoded into the raw bytes.
+fn data_key_bytes(obj: &DynamicObject, kind: &str, key: &str) -> anyhow::Result<Vec<u8>> {
+    let not_found = || anyhow::Error::msg("Key not found!");
+
+    if let Some(value) = obj.data["binaryData"][key].as_str() {
+        return Ok(STANDARD.decode(value)?);
+    }
+
+    let value = obj.data["data"][key].as_str().ok_or_else(not_found)?;
+    match kind {
+        "Secret" => Ok(STANDARD.decode(value)?),
+        _ => Ok(value.as_bytes().to_vec()),
+    }
+}
+
+/// Json patch setting a key of a ConfigMap or Secret. Keys of a ConfigMap's binaryData stay
+/// binary, new keys go in data, which only holds text for ConfigMaps.
+fn data_key_patch(
+    obj: &DynamicObject,
+    kind: &str,
+    key: &str,
+    value: Vec<u8>,
+) -> anyhow::Result<serde_json::Value> {
+    let (field, value) = match obj.data["binaryData"][key].is_string() {
+        true => ("binaryData", STANDARD.encode(value)),
+        false if kind == "Secret" => ("data", STANDARD.encode(value)),
+        false => ("data", String::from_utf8(value)?),
+    };
+
+    // Keys are escaped as described by the JSON pointer spec (RFC 6901).
+    let path = format!("/{}/{}", field, key.replace('~', "~0").replace('/', "~1"));
+    Ok(match obj.data[field].is_object() {
+        true => json!([{ "op": "add", "path": path, "value": value }]),
+        false => json!([{ "op": "add", "path": format!("/{}", field), "value": { key: value } }]),
+    })
+}
+
+/// How the last sync of the children of a directory with the cluster went.
+#[derive(Debug, Clone, Default)]
+struct SyncState {
+    /// Last time the children were listed successfully.
+    synced: Option<DateTime<Utc>>,
+    /// Why the last attempt failed, if it did.
+    error: Option<String>,
+}
+
+/// Number of patch outcomes kept around for the `.patch-result` files.
+const RECENT_PATCH_RESULTS: usize = 100;
+
+/// Name of the file that confirms deleting a namespace with `-o allow_recursive_delete`.
+const CONFIRM_DELETE_FILE: &str = ".confirm";
+
+/// Extended attributes of api-resource directories.
+const XATTRS: [&str; 5] = [
+    "user.kubefs.group",
+    "user.kubefs.version",
+    "user.kubefs.scope",
+    "user.kubefs.shortnames",
+    "user.kubefs.verbs",
+];
+
+/// Appended to manifests cut off by `-o max_manifest_bytes`.
+const TRUNCATED_MARKER: &str = "\n# ...truncated by kubefs...\n";
+
+/// Name of the link to the manifest of a resource in its directory.
+const CURRENT_LINK: &str = "current";
+
+/// Name of the link to the manifest of the owner of a resource in its directory.
+const OWNER_LINK: &str = "owner";
+
+/// Name of the label overview in a kind directory.
+const LABELS_DIRECTORY: &str = "labels";
+
+/// Kinds that get a `<name>.scale`.
+const SCALABLE_KINDS: [&str; 3] = ["Deployment", "StatefulSet", "ReplicaSet"];
+
+/// Kinds split into `ready` and `not-ready` with `-o health_split`.
+const HEALTH_KINDS: [&str; 5] = [
+    "Pod",
+    "Deployment",
+    "StatefulSet",
+    "ReplicaSet",
+    "DaemonSet",
+];
+
+/// Annotation `kubectl apply` stores the configuration it applied in.
+const LAST_APPLIED_ANNOTATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Renders how a resource drifted from its last applied configuration, one hunk per field. Only
+/// the fields in the applied configuration are compared, everything else is set by the api
+/// server or controllers and isn't drift. Empty when nothing drifted, like diff(1).
+fn format_applied_diff(obj: &DynamicObject) -> anyhow::Result<String> {
+    let applied = match obj.annotations().get(LAST_APPLIED_ANNOTATION) {
+        Some(applied) => serde_json::from_str(applied)?,
+        None => {
+            return Ok(format!(
+                "{} has no {} annotation, it wasn't created with kubectl apply\n",
+                obj.name_any(),
+                LAST_APPLIED_ANNOTATION
+            ))
+        }
+    };
+
+    let mut live = obj.clone();
+    live.metadata.managed_fields = None;
+    live.annotations_mut().remove(LAST_APPLIED_ANNOTATION);
+    let live = serde_json::to_value(&live)?;
+
+    let mut hunks = Vec::new();
+    diff_json(String::new(), &applied, &live, &mut hunks);
+
+    match hunks.is_empty() {
+        true => Ok(String::new()),
+        false => Ok(format!("--- last-applied\n+++ live\n{}", hunks.concat())),
+    }
+}
+
+/// Walks the fields of `applied`, adding a hunk for every one that's different or missing in
+/// `live`. Lists are compared item by item.
+fn diff_json(
+    path: String,
+    applied: &serde_json::Value,
+    live: &serde_json::Value,
+    hunks: &mut Vec<String>,
+) {
+    use serde_json::Value;
+
+    match (applied, live) {
+        (Value::Object(applied), Value::Object(live)) => {
+            for (key, value) in applied {
+                let path = format!("{}.{}", path, key);
+                match live.get(key) {
+                    Some(live) => diff_json(path, value, live, hunks),
+                    None => hunks.push(format!("@@ {} @@\n-{}\n", path, value)),
+                }
+            }
+        }
+        (Value::Array(applied), Value::Array(live)) => {
+            for (i, value) in applied.iter().enumerate() {
+                let path = format!("{}[{}]", path, i);
+                match live.get(i) {
+                    Some(live) => diff_json(path, value, live, hunks),
+                    None => hunks.push(format!("@@ {} @@\n-{}\n", path, value)),
+                }
+            }
+            for (i, value) in live.iter().enumerate().skip(applied.len()) {
+                hunks.push(format!("@@ {}[{}] @@\n+{}\n", path, i, value));
+            }
+        }
+        (applied, live) if applied != live => {
+            hunks.push(format!("@@ {} @@\n-{}\n+{}\n", path, applied, live));
+        }
+        _ => {}
+    }
+}
+
+/// Whether the node is a view of a manifest that is only there once looked up by name, those never
+/// show up in a directory listing.
+fn is_manifest_view(node: &KubeFileNode) -> bool {
+    matches!(
+        node,
+        KubeFileNode::ProjectionFile(..)
+            | KubeFileNode::VersionedResourceFile(..)
+            | KubeFileNode::OwnedFieldsFile(..)
+    )
+}
+
+/// Names come from the cluster, e.g. the keys of a ConfigMap, and the api server validates
+/// those for its own rules rather than ours. Leave out anything that can't be a path component.
+fn is_valid_file_name(name: &str, node: &KubeFileNode) -> bool {
+    match node {
+        KubeFileNode::Virtual(_) => true,
+        _ => !(name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0'])),
+    }
+}
+
+/// Namespaces Kubernetes creates for itself.
+const SYSTEM_NAMESPACES: [&str; 3] = ["kube-system", "kube-public", "kube-node-lease"];
+
+/// Labels marking a namespace as part of the platform rather than a workload, OpenShift puts
+/// `openshift.io/run-level` on the namespaces of its control plane.
+const SYSTEM_NAMESPACE_LABELS: [&str; 1] = ["openshift.io/run-level"];
+
+fn is_system_namespace(namespace: &DynamicObject) -> bool {
+    SYSTEM_NAMESPACES.contains(&namespace.name_any().as_str())
+        || SYSTEM_NAMESPACE_LABELS
+            .iter()
+            .any(|label| namespace.labels().contains_key(*label))
+}
+
+/// Upper bound on the lines in `.kubefs/inodes`, so huge trees don't produce huge reads.
+const MAX_LISTED_INODES: usize = 10_000;
+
+/// Secret type Helm uses to store releases.
+const HELM_RELEASE_TYPE: &str = "helm.sh/release.v1";
+
+fn is_helm_release(obj: &DynamicObject) -> bool {
+    obj.data["type"].as_str() == Some(HELM_RELEASE_TYPE)
+}
+
+/// Helm gzips the release, base64 encodes it and stores that as the value of a secret, which
+/// gets base64 encoded once more. Replace it with the json release it holds.
+fn decode_helm_release(obj: &mut DynamicObject) -> anyhow::Result<()> {
+    let encoded = obj.data["data"]["release"]
+        .as_str()
+        .ok_or(anyhow::Error::msg("Secret has no release key!"))?;
+
+    let gzipped = STANDARD.decode(STANDARD.decode(encoded)?)?;
+
+    let mut release = String::new();
+    GzDecoder::new(gzipped.as_slice()).read_to_string(&mut release)?;
+
+    obj.data["data"]["release"] = serde_json::from_str(&release)?;
+    Ok(())
+}
+
+/// Renders where the mount connects to, one `key: value` line per detail.
+fn format_connection(connection: Option<Connection>, context: Option<String>) -> String {
+    let none = || String::from("<none>");
+    let mut out = format!("context: {}\n", context.unwrap_or_else(none));
+
+    if let Some(connection) = connection {
+        out.push_str(&format!("server: {}\n", connection.server));
+        out.push_str(&format!("auth: {}\n", connection.auth));
+        out.push_str(&format!(
+            "impersonate: {}\n",
+            connection.impersonate.unwrap_or_else(none)
+        ));
+        out.push_str(&format!(
+            "tls server name: {}\n",
+            connection.tls_server_name.unwrap_or_else(none)
+        ));
+        out.push_str(&format!("insecure: {}\n", connection.insecure));
+    }
+
+    out
+}
+
+/// Whether a pod or workload is ready. Pods go by their Ready condition, or by their containers
+/// before they have one, workloads by whether every replica they want is ready.
+fn is_ready(obj: &DynamicObject, kind: &str) -> bool {
+    let status = &obj.data["status"];
+    match kind {
+        "Pod" => {
+            let condition = status["conditions"]
+                .as_array()
+                .and_then(|conditions| conditions.iter().find(|c| c["type"] == "Ready"));
+
+            match condition {
+                Some(condition) => condition["status"] == "True",
+                None => status["containerStatuses"]
+                    .as_array()
+                    .map(|s| !s.is_empty() && s.iter().all(|s| s["ready"] == true))
+                    .unwrap_or(false),
+            }
+        }
+        "DaemonSet" => {
+            let wanted = status["desiredNumberScheduled"].as_u64().unwrap_or(0);
+            status["numberReady"].as_u64().unwrap_or(0) >= wanted
+        }
+        _ => {
+            let wanted = obj.data["spec"]["replicas"].as_u64().unwrap_or(1);
+            status["readyReplicas"].as_u64().unwrap_or(0) >= wanted
+        }
+    }
+}
+
+/// Renders the container statuses of a pod as a small table: one line per container with its
+/// image, ready state, restart count and the reason it last terminated.
+fn format_pod_summary(pod: &DynamicObject) -> String {
+    let empty = Vec::new();
+    let statuses = pod.data["status"]["containerStatuses"]
+        .as_array()
+        .unwrap_or(&empty);
+
+    let mut rows = vec![vec![
+        String::from("CONTAINER"),
+        String::from("IMAGE"),
+        String::from("READY"),
+        String::from("RESTARTS"),
+        String::from("LAST TERMINATION"),
+    ]];
+
+    for status in statuses {
+        let last_termination = status["lastState"]["terminated"]["reason"]
+            .as_str()
+            .unwrap_or("<none>");
+
+        rows.push(vec![
+            status["name"].as_str().unwrap_or_default().into(),
+            status["image"].as_str().unwrap_or_default().into(),
+            status["ready"].as_bool().unwrap_or(false).to_string(),
+            status["restartCount"].as_u64().unwrap_or(0).to_string(),
+            last_termination.into(),
+        ]);
+    }
+
+    format_table(&rows)
+}
+
+/// Renders what the resource quotas of a namespace allow against what is used, followed by the
+/// defaults and bounds its limit ranges put on containers and pods.
+fn format_quota(quotas: &[DynamicObject], limit_ranges: &[DynamicObject]) -> String {
+    let mut out = String::new();
+
+    if !quotas.is_empty() {
+        let mut rows = vec![vec![
+            String::from("QUOTA"),
+            String::from("RESOURCE"),
+            String::from("USED"),
+            String::from("HARD"),
+        ]];
+
+        for quota in quotas {
+            let used = &quota.data["status"]["used"];
+            let hard = quota.data["status"]["hard"]
+                .as_object()
+                .or_else(|| quota.data["spec"]["hard"].as_object());
+
+            for (resource, limit) in hard.into_iter().flatten() {
+                rows.push(vec![
+                    quota.name_any(),
+                    resource.clone(),
+                    used[resource].as_str().unwrap_or("0").into(),
+                    limit.as_str().unwrap_or_default().into(),
+                ]);
+            }
+        }
+
+        out.push_str(&format_table(&rows));
+    }
+
+    if !limit_ranges.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        let mut rows = vec![vec![
+            String::from("LIMITRANGE"),
+            String::from("TYPE"),
+            String::from("RESOURCE"),
+            String::from("MIN"),
+            String::from("MAX"),
+            String::from("DEFAULT REQUEST"),
+            String::from("DEFAULT LIMIT"),
+        ]];
+
+        let empty = Vec::new();
+        for range in limit_ranges {
+            let limits = range.data["spec"]["limits"].as_array().unwrap_or(&empty);
+
+            for limit in limits {
+                let mut resources: Vec<&String> = ["min", "max", "defaultRequest", "default"]
+                    .iter()
+                    .filter_map(|field| limit[field].as_object())
+                    .flat_map(|values| values.keys())
+                    .collect();
+                resources.sort();
+                resources.dedup();
+
+                for resource in resources {
+                    let value = |field: &str| limit[field][resource].as_str().unwrap_or("-").into();
+                    rows.push(vec![
+                        range.name_any(),
+                        limit["type"].as_str().unwrap_or_default().into(),
+                        resource.clone(),
+                        value("min"),
+                        value("max"),
+                        value("defaultRequest"),
+                        value("default"),
+                    ]);
+                }
+            }
+        }
+
+        out.push_str(&format_table(&rows));
+    }
+
+    out
+}
+
+/// Adds the fields of one FieldsV1 set to another, a manager has an entry per operation and
+/// subresource it touched.
+fn merge_field_sets(
+    into: &mut serde_json::Map<String, serde_json::Value>,
+    from: &serde_json::Map<String, serde_json::Value>,
+) {
+    for (key, value) in from {
+        match (into.get_mut(key), value) {
+            (Some(serde_json::Value::Object(into)), serde_json::Value::Object(from)) => {
+                merge_field_sets(into, from)
+            }
+            (Some(_), _) => {}
+            (None, _) => {
+                into.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Cuts a value down to the fields in a FieldsV1 set, the format managedFields are kept in.
+/// Its keys are `f:<field>` for fields of a map, `k:<keys>` for items of a list picked by their
+/// key fields, `v:<value>` for items of a set and `i:<index>` for items by position, each holding
+/// the set of fields owned underneath. An empty set owns the whole value, `.` only marks that
+/// the value itself is owned as well. None when nothing in the value is owned.
+fn project_field_set(
+    value: &serde_json::Value,
+    fields: &serde_json::Map<String, serde_json::Value>,
+) -> Option<serde_json::Value> {
+    let owned = |value: &serde_json::Value, fields: &serde_json::Value| match fields.as_object() {
+        Some(fields) if fields.keys().any(|k| k != ".") => project_field_set(value, fields),
+        _ => Some(value.clone()),
+    };
+
+    match value {
+        serde_json::Value::Object(map) => {
+            let projected: serde_json::Map<String, serde_json::Value> = fields
+                .iter()
+                .filter_map(|(key, sub)| {
+                    let name = key.strip_prefix("f:")?;
+                    Some((name.to_string(), owned(map.get(name)?, sub)?))
+                })
+                .collect();
+            Some(serde_json::Value::Object(projected))
+        }
+        serde_json::Value::Array(items) => {
+            let projected: Vec<serde_json::Value> = items
+                .iter()
+                .enumerate()
+                .filter_map(|(index, item)| {
+                    let (_, sub) = fields.iter().find(|(key, _)| {
+                        if let Some(keys) = key.strip_prefix("k:") {
+                            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(keys)
+                                .is_ok_and(|keys| keys.iter().all(|(k, v)| &item[k] == v))
+                        } else if let Some(set_value) = key.strip_prefix("v:") {
+                            serde_json::from_str::<serde_json::Value>(set_value)
+                                .is_ok_and(|v| &v == item)
+                        } else if let Some(i) = key.strip_prefix("i:") {
+                            i.parse() == Ok(index)
+                        } else {
+                            false
+                        }
+                    })?;
+                    owned(item, sub)
+                })
+                .collect();
+            Some(serde_json::Value::Array(projected))
+        }
+        _ => None,
+    }
+}
+
+/// Renders events like `kubectl events`, oldest first. Repeated events are reported once with
+/// the number of times they happened.
+fn format_events(mut events: Vec<KubeEvent>) -> String {
+    let last_seen = |e: &KubeEvent| {
+        e.last_timestamp
+            .as_ref()
+            .map(|t| t.0)
+            .or_else(|| e.event_time.as_ref().map(|t| t.0))
+            .or_else(|| e.metadata.creation_timestamp.as_ref().map(|t| t.0))
+    };
+    events.sort_by_key(last_seen);
+
+    let mut rows = vec![vec![
+        String::from("LAST SEEN"),
+        String::from("TYPE"),
+        String::from("REASON"),
+        String::from("COUNT"),
+        String::from("MESSAGE"),
+    ]];
+
+    for event in &events {
+        rows.push(vec![
+            format_age(last_seen(event)),
+            event.type_.clone().unwrap_or_default(),
+            event.reason.clone().unwrap_or_default(),
+            event.count.unwrap_or(1).to_string(),
+            event.message.clone().unwrap_or_default().replace('\n', " "),
+        ]);
+    }
+
+    format_table(&rows)
+}
+
+/// Names of the containers of a pod, init containers first as they run first.
+fn container_names(pod: &DynamicObject) -> Vec<String> {
+    ["initContainers", "containers"]
+        .iter()
+        .filter_map(|field| pod.data["spec"][field].as_array())
+        .flatten()
+        .filter_map(|c| c["name"].as_str().map(String::from))
+        .collect()
+}
+
+/// Spec of a container or init container of a pod.
+fn container_spec<'a>(pod: &'a DynamicObject, name: &str) -> Option<&'a serde_json::Value> {
+    ["initContainers", "containers"]
+        .iter()
+        .filter_map(|field| pod.data["spec"][field].as_array())
+        .flatten()
+        .find(|c| c["name"] == name)
+}
+
+/// State, readiness and restarts of a single container of a pod.
+fn format_container_status(pod: &DynamicObject, name: &str) -> String {
+    let status = ["initContainerStatuses", "containerStatuses"]
+        .iter()
+        .filter_map(|field| pod.data["status"][field].as_array())
+        .flatten()
+        .find(|c| c["name"] == name);
+
+    let status = match status {
+        Some(status) => status,
+        None => return String::from("state: <unknown>\n"),
+    };
+
+    let state = &status["state"];
+    let state = if state["running"].is_object() {
+        format!(
+            "running since {}",
+            state["running"]["startedAt"].as_str().unwrap_or_default()
+        )
+    } else if state["terminated"].is_object() {
+        format!(
+            "terminated ({}, exit code {})",
+            state["terminated"]["reason"].as_str().unwrap_or_default(),
+            state["terminated"]["exitCode"].as_i64().unwrap_or_default()
+        )
+    } else if state["waiting"].is_object() {
+        format!(
+            "waiting ({})",
+            state["waiting"]["reason"].as_str().unwrap_or_default()
+        )
+    } else {
+        String::from("<unknown>")
+    };
+
+    format!(
+        "state: {}\nready: {}\nrestarts: {}\n",
+        state,
+        status["ready"].as_bool().unwrap_or(false),
+        status["restartCount"].as_u64().unwrap_or(0)
+    )
+}
+
+/// Renders the replicas of a workload. When an autoscaler targets it, its bounds, what it wants,
+/// its metrics against their targets and its conditions follow, telling why the replicas are
+/// what they are.
+fn format_scale(workload: &DynamicObject, autoscaler: Option<&DynamicObject>) -> String {
+    let mut out = format!(
+        "replicas: {}\nready: {}\n",
+        workload.data["spec"]["replicas"].as_u64().unwrap_or(1),
+        workload.data["status"]["readyReplicas"]
+            .as_u64()
+            .unwrap_or(0)
+    );
+
+    let autoscaler = match autoscaler {
+        Some(autoscaler) => autoscaler,
+        None => {
+            out.push_str("autoscaler: <none>\n");
+            return out;
+        }
+    };
+    let spec = &autoscaler.data["spec"];
+    let status = &autoscaler.data["status"];
+
+    out.push_str(&format!(
+        "autoscaler: {}\nminReplicas: {}\nmaxReplicas: {}\ncurrentReplicas: {}\ndesiredReplicas: {}\n",
+        autoscaler.name_any(),
+        spec["minReplicas"].as_u64().unwrap_or(1),
+        spec["maxReplicas"].as_u64().unwrap_or_default(),
+        status["currentReplicas"].as_u64().unwrap_or_default(),
+        status["desiredReplicas"].as_u64().unwrap_or_default()
+    ));
+
+    let metrics = spec["metrics"].as_array().into_iter().flatten();
+    let current_metrics = status["currentMetrics"].as_array();
+    let mut lines = Vec::new();
+    for metric in metrics {
+        let name = metric_name(metric);
+        let current = current_metrics
+            .into_iter()
+            .flatten()
+            .find(|m| m["type"] == metric["type"] && metric_name(m) == name)
+            .and_then(|m| metric_value(&m[metric_source(m)]["current"]));
+
+        lines.push(format!(
+            "  {}: {} / {}\n",
+            name,
+            current.unwrap_or_else(|| String::from("<unknown>")),
+            metric_value(&metric[metric_source(metric)]["target"]).unwrap_or_default()
+        ));
+    }
+    if !lines.is_empty() {
+        out.push_str(&format!("metrics:\n{}", lines.concat()));
+    }
+
+    let conditions = status["conditions"].as_array().into_iter().flatten();
+    let lines: Vec<String> = conditions
+        .map(|c| {
+            format!(
+                "  {}: {} ({})\n",
+                c["type"].as_str().unwrap_or_default(),
+                c["status"].as_str().unwrap_or_default(),
+                c["reason"].as_str().unwrap_or_default()
+            )
+        })
+        .collect();
+    if !lines.is_empty() {
+        out.push_str(&format!("conditions:\n{}", lines.concat()));
+    }
+
+    out
+}
+
+/// Field of an autoscaler metric holding its source, `Resource` metrics keep theirs in
+/// `resource` and so on.
+fn metric_source(metric: &serde_json::Value) -> String {
+    let kind = metric["type"].as_str().unwrap_or_default();
+    let mut chars = kind.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Name of an autoscaler metric, e.g. `cpu` or the name of a custom metric.
+fn metric_name(metric: &serde_json::Value) -> String {
+    let source = &metric[metric_source(metric)];
+    source["name"]
+        .as_str()
+        .or_else(|| source["metric"]["name"].as_str())
+        .unwrap_or_default()
+        .into()
+}
+
+/// A metric target or current value, the way `kubectl get hpa` shows it.
+fn metric_value(value: &serde_json::Value) -> Option<String> {
+    if let Some(utilization) = value["averageUtilization"].as_u64() {
+        return Some(format!("{}%", utilization));
+    }
+
+    value["averageValue"]
+        .as_str()
+        .or_else(|| value["value"].as_str())
+        .map(String::from)
+}
+
+/// Renders every api service, similar to `kubectl get apiservices`. An unavailable service gets
+/// the reason and message of its `Available` condition appended.
+fn format_api_services(services: &[DynamicObject]) -> String {
+    let mut rows = vec![vec![
+        String::from("NAME"),
+        String::from("SERVICE"),
+        String::from("AVAILABLE"),
+        String::from("AGE"),
+    ]];
+
+    for service in services {
+        let backend = &service.data["spec"]["service"];
+        let backend = match backend["name"].as_str() {
+            Some(name) => format!(
+                "{}/{}",
+                backend["namespace"].as_str().unwrap_or_default(),
+                name
+            ),
+            None => String::from("Local"),
+        };
+
+        let empty = Vec::new();
+        let available = service.data["status"]["conditions"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .find(|c| c["type"] == "Available")
+            .map(|c| match c["status"].as_str() {
+                Some("True") => String::from("True"),
+                status => format!(
+                    "{} ({}: {})",
+                    status.unwrap_or("Unknown"),
+                    c["reason"].as_str().unwrap_or_default(),
+                    c["message"].as_str().unwrap_or_default()
+                ),
+            })
+            .unwrap_or(String::from("Unknown"));
+
+        rows.push(vec![
+            service.name_any(),
+            backend,
+            available,
+            format_age(service.metadata.creation_timestamp.clone().map(|t| t.0)),
+        ]);
+    }
+
+    format_table(&rows)
+}
+
+/// Renders the usage of every node, similar to `kubectl top nodes`.
+fn format_node_metrics(metrics: &[DynamicObject]) -> String {
+    let mut rows = vec![vec![
+        String::from("NAME"),
+        String::from("CPU"),
+        String::from("MEMORY"),
+    ]];
+
+    for node in metrics {
+        rows.push(vec![
+            node.name_any(),
+            node.data["usage"]["cpu"]
+                .as_str()
+                .unwrap_or_default()
+                .into(),
+            node.data["usage"]["memory"]
+                .as_str()
+                .unwrap_or_default()
+                .into(),
+        ]);
+    }
+
+    format_table(&rows)
+}
+
+/// Renders the usage of every container in a pod, similar to `kubectl top pod --containers`.
+fn format_pod_metrics(metrics: &DynamicObject) -> String {
+    let empty = Vec::new();
+    let containers = metrics.data["containers"].as_array().unwrap_or(&empty);
+
+    let mut rows = vec![vec![
+        String::from("CONTAINER"),
+        String::from("CPU"),
+        String::from("MEMORY"),
+    ]];
+
+    for container in containers {
+        rows.push(vec![
+            container["name"].as_str().unwrap_or_default().into(),
+            container["usage"]["cpu"]
+                .as_str()
+                .unwrap_or_default()
+                .into(),
+            container["usage"]["memory"]
+                .as_str()
+                .unwrap_or_default()
+                .into(),
+        ]);
+    }
+
+    format_table(&rows)
+}
+
+/// Columns of the pod table, the ones with a priority are only shown with `-o wide` like they
+/// are by `kubectl get pods -o wide`.
+fn pod_columns() -> Vec<CustomResourceColumnDefinition> {
+    let column =
+        |name: &str, type_: &str, json_path: &str, priority: i32| CustomResourceColumnDefinition {
+            name: name.into(),
+            type_: type_.into(),
+            json_path: json_path.into(),
+            priority: Some(priority),
+            ..Default::default()
+        };
+
+    vec![
+        column("Age", "date", ".metadata.creationTimestamp", 0),
+        column("IP", "string", ".status.podIP", 1),
+        column("Node", "string", ".spec.nodeName", 1),
+        column("Host IP", "string", ".status.hostIP", 1),
+    ]
+}
+
+/// Renders a `kubectl get` style table for the given objects. CRDs that define
+/// `additionalPrinterColumns` get those columns, everything else falls back to NAME and AGE.
+fn format_resource_table(
+    objs: &[DynamicObject],
+    columns: &[CustomResourceColumnDefinition],
+    wide: bool,
+) -> String {
+    // Columns with a non-zero priority are only shown by `kubectl get -o wide`.
+    let columns: Vec<&CustomResourceColumnDefinition> = columns
+        .iter()
+        .filter(|c| wide || c.priority.unwrap_or(0) == 0)
+        .collect();
+
+    let mut header = vec![String::from("NAME")];
+    match columns.is_empty() {
+        true => header.push(String::from("AGE")),
+        false => header.extend(columns.iter().map(|c| c.name.to_uppercase())),
+    }
+
+    let mut rows = vec![header];
+
+    for obj in objs {
+        let mut row = vec![obj.name_any()];
+
+        if columns.is_empty() {
+            row.push(format_age(obj.creation_timestamp().map(|t| t.0)));
+        }
+
+        let json = serde_json::to_value(obj).unwrap_or_default();
+        for column in &columns {
+            let value = json_path(&json, column.json_path.as_str());
+            let cell = match (column.type_.as_str(), value) {
+                (_, None) | (_, Some(serde_json::Value::Null)) => String::from("<none>"),
+                ("date", Some(serde_json::Value::String(date))) => {
+                    format_age(date.parse::<DateTime<Utc>>().ok())
+                }
+                (_, Some(serde_json::Value::String(value))) => value.clone(),
+                (_, Some(value)) => value.to_string(),
+            };
+            row.push(cell);
+        }
+
+        rows.push(row);
+    }
+
+    format_table(&rows)
+}
+
+/// Evaluates the simple subset of JSONPath used by printer columns: a chain of `.field` and
+/// `[index]` selectors. Anything fancier (filters, wildcards) doesn't resolve.
+fn json_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    let path = path.trim().trim_start_matches('{').trim_end_matches('}');
+    let path = path.strip_prefix('$').unwrap_or(path);
+
+    let mut current = value;
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (field, indices) = match segment.find('[') {
+            Some(i) => segment.split_at(i),
+            None => (segment, ""),
+        };
+
+        if !field.is_empty() {
+            current = current.get(field)?;
+        }
+
+        for index in indices.split('[').filter(|i| !i.is_empty()) {
+            let index: usize = index.strip_suffix(']')?.parse().ok()?;
+            current = current.get(index)?;
+        }
+    }
+
+    Some(current)
+}
+
+/// Whether `path` is made up of the `.field` and `[index]` selectors `json_path` understands.
+fn is_json_path(path: &str) -> bool {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    if !path.starts_with('.') {
+        return false;
+    }
+
+    path.split('.').skip(1).all(|segment| {
+        let (field, indices) = match segment.find('[') {
+            Some(i) => segment.split_at(i),
+            None => (segment, ""),
+        };
+
+        (!field.is_empty() || !indices.is_empty())
+            && !field.contains(']')
+            && indices
+                .strip_prefix('[')
+                .map(|i| {
+                    i.split('[').all(|index| {
+                        index
+                            .strip_suffix(']')
+                            .is_some_and(|n| n.parse::<usize>().is_ok())
+                    })
+                })
+                .unwrap_or(true)
+    })
+}
+
+/// One line per failed request, newest first, e.g.
+/// `2023-03-01T10:00:00+00:00   listing secrets in default: forbidden`.
+fn format_api_errors(errors: &[ApiError]) -> String {
+    let rows: Vec<Vec<String>> = errors
+        .iter()
+        .map(|e| vec![e.time.to_rfc3339(), e.message.clone()])
+        .collect();
+
+    format_table(&rows)
+}
+
+/// Renders the discovered kinds, similar to `kubectl api-resources`.
+fn format_api_resources(resources: &[(ApiResource, ApiCapabilities)]) -> String {
+    let mut rows = vec![vec![
+        String::from("NAME"),
+        String::from("APIVERSION"),
+        String::from("NAMESPACED"),
+        String::from("KIND"),
+        String::from("VERBS"),
+    ]];
+
+    for (resource, capabilities) in resources {
+        rows.push(vec![
+            resource.plural.clone(),
+            resource.api_version.clone(),
+            (capabilities.scope == Scope::Namespaced).to_string(),
+            resource.kind.clone(),
+            capabilities.operations.join(","),
+        ]);
+    }
+
+    format_table(&rows)
+}
+
+/// Renders the documentation of a kind from the OpenAPI document of its group version, the way
+/// `kubectl explain --recursive` does but with the description of every field. Schemas used in
+/// several places, like the containers of a pod, are only expanded the first time around.
+fn format_explain(resource: &ApiResource, document: &serde_json::Value) -> Option<String> {
+    let schemas = document.pointer("/components/schemas")?.as_object()?;
+    let (name, schema) = schemas.iter().find(|(_, schema)| {
+        let gvks = schema["x-kubernetes-group-version-kind"].as_array();
+        gvks.into_iter().flatten().any(|gvk| {
+            gvk["group"] == resource.group.as_str()
+                && gvk["version"] == resource.version.as_str()
+                && gvk["kind"] == resource.kind.as_str()
+        })
+    })?;
+
+    let mut fields = String::new();
+    let mut expanded = HashSet::from([name.as_str()]);
+    let documented = format_explain_fields(schemas, schema, 1, &mut expanded, &mut fields);
+
+    let description = schema["description"].as_str().unwrap_or_default();
+    if description.is_empty() && !documented {
+        return None;
+    }
+
+    let mut out = format!(
+        "KIND:     {}\nVERSION:  {}\n\nDESCRIPTION:\n",
+        resource.kind, resource.api_version
+    );
+    match description {
+        "" => out.push_str("     <empty>\n"),
+        description => out.push_str(&wrap_text(description, 5)),
+    }
+    out.push_str(&format!("\nFIELDS:\n{}", fields));
+
+    Some(out)
+}
+
+/// Writes the fields of `schema` and the ones nested in them, telling whether any of them has a
+/// description.
+fn format_explain_fields<'a>(
+    schemas: &'a serde_json::Map<String, serde_json::Value>,
+    schema: &'a serde_json::Value,
+    depth: usize,
+    expanded: &mut HashSet<&'a str>,
+    out: &mut String,
+) -> bool {
+    let properties = match schema["properties"].as_object() {
+        Some(properties) => properties,
+        None => return false,
+    };
+    let mut documented = false;
+    let required = schema["required"].as_array();
+    let indent = "   ".repeat(depth);
+
+    for (name, field) in properties {
+        let required = match required.into_iter().flatten().any(|r| r == name.as_str()) {
+            true => " -required-",
+            false => "",
+        };
+        out.push_str(&format!(
+            "{}{}\t<{}>{}\n",
+            indent,
+            name,
+            explain_type(schemas, field),
+            required
+        ));
+
+        let (target, reference) = explain_target(schemas, field);
+        let description = field["description"]
+            .as_str()
+            .or_else(|| target["description"].as_str());
+        if let Some(description) = description {
+            out.push_str(&wrap_text(description, indent.len() + 2));
+            documented = true;
+        }
+
+        if reference.is_none_or(|reference| expanded.insert(reference)) {
+            documented |= format_explain_fields(schemas, target, depth + 1, expanded, out);
+        }
+    }
+
+    documented
+}
+
+/// Follows a `$ref`, which OpenAPI v3 wraps in an `allOf` when the field has a description of
+/// its own.
+fn explain_resolve<'a>(
+    schemas: &'a serde_json::Map<String, serde_json::Value>,
+    schema: &'a serde_json::Value,
+) -> (&'a serde_json::Value, Option<&'a str>) {
+    let reference = schema["$ref"]
+        .as_str()
+        .or_else(|| schema["allOf"][0]["$ref"].as_str())
+        .and_then(|r| r.rsplit('/').next());
+
+    match reference.and_then(|r| schemas.get_key_value(r)) {
+        Some((name, schema)) => (schema, Some(name.as_str())),
+        None => (schema, None),
+    }
+}
+
+/// The schema whose fields a field holds, looking through arrays and maps.
+fn explain_target<'a>(
+    schemas: &'a serde_json::Map<String, serde_json::Value>,
+    schema: &'a serde_json::Value,
+) -> (&'a serde_json::Value, Option<&'a str>) {
+    let (schema, reference) = explain_resolve(schemas, schema);
+    match schema["type"].as_str() {
+        Some("array") => explain_target(schemas, &schema["items"]),
+        Some("object") if schema["additionalProperties"].is_object() => {
+            explain_target(schemas, &schema["additionalProperties"])
+        }
+        _ => (schema, reference),
+    }
+}
+
+/// Type of a field the way `kubectl explain` shows it, e.g. `[]Container` or `map[string]string`.
+fn explain_type(
+    schemas: &serde_json::Map<String, serde_json::Value>,
+    schema: &serde_json::Value,
+) -> String {
+    let (schema, reference) = explain_resolve(schemas, schema);
+    if let Some(reference) = reference {
+        return reference.rsplit('.').next().unwrap_or(reference).into();
+    }
+
+    match schema["type"].as_str() {
+        Some("array") => format!("[]{}", explain_type(schemas, &schema["items"])),
+        Some("object") if schema["additionalProperties"].is_object() => format!(
+            "map[string]{}",
+            explain_type(schemas, &schema["additionalProperties"])
+        ),
+        Some("object") | None => String::from("Object"),
+        Some(other) => other.into(),
+    }
+}
+
+/// Wraps `text` at 80 columns, indenting every line by `indent` spaces.
+fn wrap_text(text: &str, indent: usize) -> String {
+    let mut out = String::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && indent + line.len() + 1 + word.len() > 80 {
+                out.push_str(&format!("{}{}\n", " ".repeat(indent), line));
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        match line.is_empty() {
+            true => out.push('\n'),
+            false => out.push_str(&format!("{}{}\n", " ".repeat(indent), line)),
+        }
+    }
+    out
+}
+
+/// Formats the time elapsed since `timestamp` the way kubectl does, e.g. `42s`, `5m`, `3h`, `7d`.
+fn format_age(timestamp: Option<DateTime<Utc>>) -> String {
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => return String::from("<unknown>"),
+    };
+
+    let seconds = (Utc::now() - timestamp).num_seconds().max(0);
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 60 * 60 * 24 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (60 * 60 * 24)),
+    }
+}
+
+/// Aligns the given rows into left-justified columns separated by three spaces.
+fn format_table(rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows {
+        for (i, col) in row.iter().enumerate() {
+            match widths.get_mut(i) {
+                Some(width) => *width = (*width).max(col.len()),
+                None => widths.push(col.len()),
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, col)| format!("{:width$}", col, width = widths[i]))
+            .collect();
+        out.push_str(line.join("   ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use kube::core::GroupVersionKind;
+    use serde_json::json;
+
+    use super::*;
+    use crate::client::mock::{discovery, list, mock_client_with, status};
+    use http::StatusCode;
+
+    /// A filesystem on a mock cluster with a `default` namespace, running whichever pods are in
+    /// `pods` at the time it's asked.
+    fn mock_fs(pods: Arc<Mutex<Vec<&'static str>>>) -> KubeVirtualFs {
+        let options = KubeFsOptions {
+            no_cache: true,
+            max_backoff: Duration::from_millis(1),
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, move |path| match path {
+            "/api/v1/namespaces" => list("Namespace", &["default"]),
+            "/api/v1/namespaces/default/pods" => list("Pod", &pods.lock().unwrap()),
+            _ => discovery(path),
+        });
+
+        KubeVirtualFs::new(client, &options).unwrap()
+    }
+
+    /// Inode of the file at `path` from the root of the mount.
+    fn lookup(fs: &mut KubeVirtualFs, path: &[&str]) -> u64 {
+        path.iter().fold(fs.root_inode(), |parent, name| {
+            let (_, attr) = fs
+                .get_file_from_parent_by_name_two(parent, name)
+                .unwrap()
+                .unwrap();
+            attr.ino
+        })
+    }
+
+    fn list_inodes(fs: &mut KubeVirtualFs, inode: u64) -> HashMap<String, u64> {
+        let files = fs.list_files_two(inode).unwrap().unwrap();
+        files.into_iter().map(|(n, attr)| (n, attr.ino)).collect()
+    }
+
+    #[test]
+    fn lists_the_kinds_of_a_namespace() {
+        let mut fs = mock_fs(Arc::new(Mutex::new(vec!["web"])));
+
+        let namespace = lookup(&mut fs, &["default"]);
+        let files = list_inodes(&mut fs, namespace);
+
+        assert!(files.contains_key("pods"));
+        assert!(files.contains_key("deployments"));
+        assert!(!files.contains_key("namespaces"));
+    }
+
+    #[test]
+    fn exposes_discovery_metadata_as_xattrs() {
+        let mut fs = mock_fs(Arc::new(Mutex::new(vec!["web"])));
+
+        let pods = lookup(&mut fs, &["default", "pods"]);
+        let namespace = lookup(&mut fs, &["default"]);
+
+        assert_eq!(fs.list_xattrs(pods), XATTRS.to_vec());
+        assert_eq!(fs.get_xattr(pods, "user.kubefs.version").unwrap(), "v1");
+        assert_eq!(
+            fs.get_xattr(pods, "user.kubefs.scope").unwrap(),
+            "Namespaced"
+        );
+        assert_eq!(fs.get_xattr(pods, "user.kubefs.shortnames").unwrap(), "po");
+        assert_eq!(
+            fs.get_xattr(pods, "user.kubefs.verbs").unwrap(),
+            "get,list,watch"
+        );
+        assert!(fs.list_xattrs(namespace).is_empty());
+        assert!(fs.get_xattr(namespace, "user.kubefs.group").is_none());
+    }
+
+    #[test]
+    fn hides_kinds_missing_a_verb() {
+        let options = KubeFsOptions {
+            no_cache: true,
+            verbs: vec![String::from("patch")],
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, |path| match path {
+            "/api/v1/namespaces" => list("Namespace", &["default"]),
+            _ => discovery(path),
+        });
+        let mut fs = KubeVirtualFs::new(client, &options).unwrap();
+
+        let namespace = lookup(&mut fs, &["default"]);
+        let files = list_inodes(&mut fs, namespace);
+
+        assert!(!files.contains_key("pods"));
+        assert!(!files.contains_key("deployments"));
+    }
+
+    #[test]
+    fn hides_the_manifests() {
+        let options = KubeFsOptions {
+            no_cache: true,
+            hide_manifest: true,
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, |path| match path {
+            "/api/v1/namespaces" => list("Namespace", &["default"]),
+            "/api/v1/namespaces/default/pods" => list("Pod", &["web"]),
+            _ => discovery(path),
+        });
+        let mut fs = KubeVirtualFs::new(client, &options).unwrap();
+
+        let pods = lookup(&mut fs, &["default", "pods"]);
+        let files = list_inodes(&mut fs, pods);
+
+        assert!(files.contains_key("web"));
+        assert!(!files.contains_key("web.yml"));
+        assert!(!files.contains_key("web.diff"));
+    }
+
+    #[test]
+    fn projects_hidden_manifests() {
+        let options = KubeFsOptions {
+            no_cache: true,
+            hide_manifest: true,
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, |path| match path {
+            "/api/v1/namespaces" => list("Namespace", &["default"]),
+            "/api/v1/namespaces/default/pods" => list("Pod", &["web"]),
+            _ => discovery(path),
+        });
+        let mut fs = KubeVirtualFs::new(client, &options).unwrap();
+
+        let pods = lookup(&mut fs, &["default", "pods"]);
+        let (_, attr) = fs
+            .get_projection_file(pods, "web.yml?.metadata.name")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(fs.read_file(attr.ino).unwrap(), b"web\n");
+        assert!(fs
+            .get_projection_file(pods, "api.yml?.metadata.name")
+            .unwrap()
+            .is_none());
+        assert!(!list_inodes(&mut fs, pods).contains_key("web.yml?.metadata.name"));
+        assert_eq!(fs.get_file(attr.ino).unwrap().0, "web.yml?.metadata.name");
+    }
+
+    #[test]
+    fn points_recent_links_at_the_manifests() {
+        let mut fs = mock_fs(Arc::new(Mutex::new(vec!["web"])));
+        let root = NodeId::new(fs.root_inode());
+        let change = ResourceChange {
+            time: SystemTime::now(),
+            namespace: "default".into(),
+            group: String::new(),
+            plural: "pods".into(),
+            name: "web".into(),
+        };
+        let recent = fs.arena_two.add(KubeFileNode::RecentDirectory, Some(root));
+        let link = fs.arena_two.add(
+            KubeFileNode::RecentLink(change.clone()),
+            Some(recent.clone()),
+        );
+        let nested = fs
+            .arena_two
+            .add(KubeFileNode::Virtual("nested".into()), Some(recent));
+        let nested_link = fs
+            .arena_two
+            .add(KubeFileNode::RecentLink(change), Some(nested));
+
+        assert_eq!(
+            fs.read_link(link.into()).unwrap(),
+            "../default/pods/web.yml"
+        );
+        assert_eq!(
+            fs.read_link(nested_link.into()).unwrap(),
+            "../../default/pods/web.yml"
+        );
+    }
+
+    #[test]
+    fn links_owned_resources_to_their_owner() {
+        let options = KubeFsOptions {
+            no_cache: true,
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, |path| match path {
+            "/api/v1/namespaces" => list("Namespace", &["default"]),
+            "/api/v1/namespaces/default/pods" => {
+                let (code, mut pods) = list("Pod", &["web", "standalone"])?;
+                pods["items"][0]["metadata"]["ownerReferences"] = json!([{
+                    "apiVersion": "apps/v1",
+                    "kind": "Deployment",
+                    "name": "web",
+                    "uid": "1",
+                    "controller": true,
+                }]);
+                Ok((code, pods))
+            }
+            _ => discovery(path),
+        });
+        let mut fs = KubeVirtualFs::new(client, &options).unwrap();
+
+        let web = lookup(&mut fs, &["default", "pods", "web"]);
+        let owner = list_inodes(&mut fs, web)["owner"];
+        let standalone = lookup(&mut fs, &["default", "pods", "standalone"]);
+
+        assert_eq!(
+            fs.read_link(owner).unwrap(),
+            "../../../default/deployments/web.yml"
+        );
+        assert!(!list_inodes(&mut fs, standalone).contains_key("owner"));
+    }
+
+    #[test]
+    fn indexes_the_files_that_get_listed() {
+        let options = KubeFsOptions {
+            no_cache: true,
+            explain: true,
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, |path| match path {
+            "/api/v1/namespaces" => list("Namespace", &["default"]),
+            _ => discovery(path),
+        });
+        let mut fs = KubeVirtualFs::new(client, &options).unwrap();
+
+        let index = lookup(&mut fs, &[".kubefs", "index.json"]);
+        let index: serde_json::Value =
+            serde_json::from_slice(&fs.read_file(index).unwrap()).unwrap();
+        let root = fs.root_inode();
+        let root = list_inodes(&mut fs, root);
+
+        for file in index["files"].as_array().unwrap() {
+            let top = file.as_str().unwrap().split('/').next().unwrap();
+            assert!(root.contains_key(top), "{} isn't listed", top);
+        }
+        let kinds = index["kinds"].as_array().unwrap();
+        let deployments = kinds.iter().find(|k| k["kind"] == "Deployment").unwrap();
+        assert!(deployments["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|f| f == "<name>.scale"));
+        assert!(deployments["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|f| f == ".explain"));
+    }
+
+    #[test]
+    fn hides_the_manifests_of_lazy_lookups() {
+        let options = KubeFsOptions {
+            no_cache: true,
+            lazy: true,
+            hide_manifest: true,
+            ..KubeFsOptions::default()
+        };
+        let client = mock_client_with(&options, |path| match path {
+            "/api/v1/namespaces" => list("Namespace", &["default"]),
+            "/api/v1/namespaces/default/pods/web" => Ok((
+                StatusCode::OK,
+                serde_json::json!({
+                    "apiVersion": "v1",
+                    "kind": "Pod",
+                    "metadata": { "name": "web", "namespace": "default" },
+                }),
+            )),
+            _ => discovery(path),
+        });
+        let mut fs = KubeVirtualFs::new(client, &options).unwrap();
+
+        let pods = lookup(&mut fs, &["default", "pods"]);
+
+        assert!(fs
+            .get_file_from_parent_by_name_two(pods, "web.yml")
+            .unwrap()
+            .is_none());
+        assert!(fs
+            .get_file_from_parent_by_name_two(pods, "web")
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn only_deletes_writable_and_confirmed_namespaces() {
+        let fs = |read_write: bool| {
+            let options = KubeFsOptions {
+                no_cache: true,
+                max_backoff: Duration::from_millis(1),
+                allow_recursive_delete: true,
+                read_write,
+                ..KubeFsOptions::default()
+            };
+            let client = mock_client_with(&options, |path| match path {
+                "/api/v1/namespaces" => list("Namespace", &["default"]),
+                "/api/v1/namespaces/default" => status(StatusCode::FORBIDDEN),
+                _ => discovery(path),
+            });
+            KubeVirtualFs::new(client, &options).unwrap()
+        };
+        let denied = |result: anyhow::Result<()>| {
+            let err = result.unwrap_err();
+            err.downcast_ref::<io::Error>().map(|e| e.kind())
+                == Some(io::ErrorKind::PermissionDenied)
+        };
+
+        let mut read_only = fs(false);
+        let namespace = lookup(&mut read_only, &["default"]);
+        assert!(read_only
+            .create_file(namespace, CONFIRM_DELETE_FILE)
+            .is_err());
+        assert!(denied(
+            read_only.remove_directory(read_only.root_inode(), "default")
+        ));
+
+        let mut writable = fs(true);
+        let namespace = lookup(&mut writable, &["default"]);
+        writable
+            .create_file(namespace, CONFIRM_DELETE_FILE)
+            .unwrap();
+        let root = writable.root_inode();
+        assert!(!denied(writable.remove_directory(root, "default")));
+        assert!(denied(writable.remove_directory(root, "default")));
+    }
+
+    #[test]
+    fn keeps_the_inodes_of_resources_that_are_still_there() {
+        let pods = Arc::new(Mutex::new(vec!["web", "db"]));
+        let mut fs = mock_fs(pods.clone());
+
+        let directory = lookup(&mut fs, &["default", "pods"]);
+        let before = list_inodes(&mut fs, directory);
+        *pods.lock().unwrap() = vec!["web", "cache"];
+        let after = list_inodes(&mut fs, directory);
+
+        assert_eq!(after["web.yml"], before["web.yml"]);
+        assert_eq!(after["web"], before["web"]);
+        assert!(after.contains_key("cache.yml"));
+        assert!(!after.contains_key("db.yml"));
+        assert!(!after.contains_key("db"));
+    }
+
+    #[test]
+    fn tells_resources_without_a_uid_apart_by_namespace() {
+        let pod = |namespace: &str| -> DynamicObject {
+            serde_json::from_value(json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": { "name": "web", "namespace": namespace },
+            }))
+            .unwrap()
+        };
+
+        let default = KubeFileNode::ResourceFile(KubeResourceNode::from(&pod("default"), "Pod"));
+        let other = KubeFileNode::ResourceFile(KubeResourceNode::from(&pod("other"), "Pod"));
+
+        assert!(default == default.clone());
+        assert!(default != other);
+    }
+
+    fn deployment(replicas: u64, applied: Option<serde_json::Value>) -> DynamicObject {
+        let mut obj: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": "web", "namespace": "default", "uid": "1234" },
+            "spec": { "replicas": replicas, "paused": false },
+            "status": { "readyReplicas": replicas },
+        }))
+        .unwrap();
+
+        if let Some(applied) = applied {
+            obj.annotations_mut()
+                .insert(LAST_APPLIED_ANNOTATION.into(), applied.to_string());
+        }
+
+        obj
+    }
+
+    #[test]
+    fn binary_data_round_trips() {
+        let bytes = vec![0x00, 0xff, 0x80, 0x0a, 0xc3];
+        let config_map: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": "certs", "namespace": "default" },
+            "data": { "text": "hello" },
+            "binaryData": { "bundle": STANDARD.encode(&bytes) },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            data_key_bytes(&config_map, "ConfigMap", "bundle").unwrap(),
+            bytes
+        );
+        assert_eq!(
+            data_key_bytes(&config_map, "ConfigMap", "text").unwrap(),
+            b"hello"
+        );
+
+        let patch = data_key_patch(&config_map, "ConfigMap", "bundle", bytes.clone()).unwrap();
+        assert_eq!(patch[0]["path"], "/binaryData/bundle");
+        let mut patched = config_map.clone();
+        patched.data["binaryData"]["bundle"] = patch[0]["value"].clone();
+        assert_eq!(
+            data_key_bytes(&patched, "ConfigMap", "bundle").unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn truncates_manifests_at_the_cap() {
+        let mut file = KubeManifestFile {
+            name: "web".into(),
+            file_type: KubeManifestType::Yaml,
+            json_style: KubeJsonStyle::Pretty,
+            data: deployment(3, None),
+            max_bytes: None,
+        };
+        let full = file.to_string();
+
+        file.max_bytes = Some(20);
+        let truncated = file.to_string();
+
+        assert_eq!(truncated, format!("{}{}", &full[..20], TRUNCATED_MARKER));
+        assert_eq!(file.get_size(), truncated.len() as u64);
+
+        file.max_bytes = Some(full.len());
+        assert_eq!(file.to_string(), full);
+    }
+
+    #[test]
+    fn renders_json_manifests_in_the_configured_style() {
+        let file = |json_style: KubeJsonStyle| KubeManifestFile {
+            name: "web".into(),
+            file_type: KubeManifestType::Json,
+            json_style,
+            data: deployment(3, None),
+            max_bytes: None,
+        };
+        let pretty = file(KubeJsonStyle::Pretty);
+        let compact = file(KubeJsonStyle::Compact);
+
+        assert!(pretty.to_string().starts_with("{\n  \""));
+        assert!(!compact.to_string().contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&pretty.to_string()).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&compact.to_string()).unwrap()
+        );
+        assert_eq!(pretty.get_size(), pretty.to_string().len() as u64);
+        assert_eq!(compact.get_size(), compact.to_string().len() as u64);
+    }
+
+    #[test]
+    fn sizes_json_manifests_by_the_served_bytes() {
+        for json_style in [KubeJsonStyle::Pretty, KubeJsonStyle::Compact] {
+            let options = KubeFsOptions {
+                no_cache: true,
+                format: KubeManifestType::Json,
+                json_style,
+                ..KubeFsOptions::default()
+            };
+            let client = mock_client_with(&options, |path| match path {
+                "/api/v1/namespaces" => list("Namespace", &["default"]),
+                "/api/v1/namespaces/default/pods" => list("Pod", &["web"]),
+                _ => discovery(path),
+            });
+            let mut fs = KubeVirtualFs::new(client, &options).unwrap();
+
+            let manifest = lookup(&mut fs, &["default", "pods", "web.json"]);
+            let (_, attr) = fs.get_file(manifest).unwrap();
+
+            assert_eq!(attr.size, fs.read_file(manifest).unwrap().len() as u64);
+        }
+    }
+
+    #[test]
+    fn explains_the_fields_of_a_kind() {
+        let pods = ApiResource::from_gvk(&GroupVersionKind::gvk("", "v1", "Pod"));
+        let document = json!({ "components": { "schemas": {
+            "io.k8s.api.core.v1.Pod": {
+                "description": "Pod is a collection of containers.",
+                "x-kubernetes-group-version-kind": [{ "group": "", "version": "v1", "kind": "Pod" }],
+                "properties": {
+                    "spec": {
+                        "description": "Specification of the pod.",
+                        "allOf": [{ "$ref": "#/components/schemas/io.k8s.api.core.v1.PodSpec" }],
+                    },
+                },
+            },
+            "io.k8s.api.core.v1.PodSpec": {
+                "required": ["containers"],
+                "properties": {
+                    "containers": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/io.k8s.api.core.v1.Container" },
+                    },
+                    "initContainers": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/io.k8s.api.core.v1.Container" },
+                    },
+                    "nodeSelector": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" },
+                    },
+                },
+            },
+            "io.k8s.api.core.v1.Container": {
+                "properties": { "image": { "type": "string", "description": "Image name." } },
+            },
+        }}});
+
+        assert_eq!(
+            format_explain(&pods, &document).unwrap(),
+            "KIND:     Pod\nVERSION:  v1\n\nDESCRIPTION:\n     Pod is a collection of containers.\n\n\
+             FIELDS:\n   \
+             spec\t<PodSpec>\n     Specification of the pod.\n      \
+             containers\t<[]Container> -required-\n         \
+             image\t<string>\n           Image name.\n      \
+             initContainers\t<[]Container>\n      \
+             nodeSelector\t<map[string]string>\n"
+        );
+
+        let deployments = ApiResource::from_gvk(&GroupVersionKind::gvk("apps", "v1", "Deployment"));
+        assert!(format_explain(&deployments, &document).is_none());
+    }
+
+    #[test]
+    fn shows_why_a_workload_has_its_replicas() {
+        let autoscaler: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "autoscaling/v2",
+            "kind": "HorizontalPodAutoscaler",
+            "metadata": { "name": "web", "namespace": "default" },
+            "spec": {
+                "scaleTargetRef": { "apiVersion": "apps/v1", "kind": "Deployment", "name": "web" },
+                "minReplicas": 2,
+                "maxReplicas": 3,
+                "metrics": [{
+                    "type": "Resource",
+                    "resource": {
+                        "name": "cpu",
+                        "target": { "type": "Utilization", "averageUtilization": 80 },
+                    },
+                }],
+            },
+            "status": {
+                "currentReplicas": 3,
+                "desiredReplicas": 3,
+                "currentMetrics": [{
+                    "type": "Resource",
+                    "resource": { "name": "cpu", "current": { "averageUtilization": 95 } },
+                }],
+                "conditions": [
+                    { "type": "ScalingLimited", "status": "True", "reason": "TooManyReplicas" },
+                ],
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            format_scale(&deployment(3, None), Some(&autoscaler)),
+            "replicas: 3\nready: 3\nautoscaler: web\nminReplicas: 2\nmaxReplicas: 3\n\
+             currentReplicas: 3\ndesiredReplicas: 3\nmetrics:\n  cpu: 95% / 80%\n\
+             conditions:\n  ScalingLimited: True (TooManyReplicas)\n"
+        );
+        assert_eq!(
+            format_scale(&deployment(1, None), None),
+            "replicas: 1\nready: 1\nautoscaler: <none>\n"
+        );
+    }
+
+    #[test]
+    fn projects_the_fields_a_manager_owns() {
+        let live = json!({
+            "metadata": { "labels": { "app": "web", "team": "a" } },
+            "spec": {
+                "replicas": 3,
+                "template": { "spec": { "containers": [
+                    { "name": "web", "image": "nginx", "resources": { "limits": { "cpu": "1" } } },
+                    { "name": "sidecar", "image": "envoy" },
+                ] } },
+            },
+        });
+        let fields = json!({
+            "f:metadata": { "f:labels": { "f:app": {} } },
+            "f:spec": { "f:template": { "f:spec": { "f:containers": {
+                "k:{\"name\":\"web\"}": { ".": {}, "f:image": {}, "f:name": {} },
+            } } } },
+        });
+
+        assert_eq!(
+            project_field_set(&live, fields.as_object().unwrap()),
+            Some(json!({
+                "metadata": { "labels": { "app": "web" } },
+                "spec": { "template": { "spec": { "containers": [
+                    { "name": "web", "image": "nginx" },
+                ] } } },
+            }))
+        );
+    }
+
+    #[test]
+    fn splits_pods_and_workloads_by_readiness() {
+        let pod = |status: serde_json::Value| -> DynamicObject {
+            serde_json::from_value(json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": { "name": "web", "namespace": "default" },
+                "status": status,
+            }))
+            .unwrap()
+        };
+
+        let ready = pod(json!({ "conditions": [{ "type": "Ready", "status": "True" }] }));
+        let crashing = pod(json!({ "conditions": [{ "type": "Ready", "status": "False" }] }));
+        let starting = pod(json!({ "containerStatuses": [{ "ready": true }, { "ready": false }] }));
+        assert!(is_ready(&ready, "Pod"));
+        assert!(!is_ready(&crashing, "Pod"));
+        assert!(!is_ready(&starting, "Pod"));
+
+        let mut scaling = deployment(3, None);
+        scaling.data["status"]["readyReplicas"] = json!(1);
+        assert!(is_ready(&deployment(3, None), "Deployment"));
+        assert!(!is_ready(&scaling, "Deployment"));
+    }
+
+    #[test]
+    fn wide_pod_table_shows_node_and_ips() {
+        let pod: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "web", "namespace": "default" },
+            "spec": { "nodeName": "node-1" },
+            "status": { "podIP": "10.0.0.5", "hostIP": "192.168.1.10" },
+        }))
+        .unwrap();
+        let pods = [pod];
+
+        assert_eq!(
+            format_resource_table(&pods, &pod_columns(), false),
+            "NAME   AGE\nweb    <none>\n"
+        );
+        assert_eq!(
+            format_resource_table(&pods, &pod_columns(), true),
+            "NAME   AGE      IP         NODE     HOST IP\n\
+             web    <none>   10.0.0.5   node-1   192.168.1.10\n"
+        );
+    }
+
+    #[test]
+    fn quota_shows_usage_and_limit_range_defaults() {
+        let quota: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ResourceQuota",
+            "metadata": { "name": "compute", "namespace": "default" },
+            "spec": { "hard": { "pods": "10" } },
+            "status": { "hard": { "pods": "10", "requests.cpu": "4" }, "used": { "pods": "3" } },
+        }))
+        .unwrap();
+        let range: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "LimitRange",
+            "metadata": { "name": "defaults", "namespace": "default" },
+            "spec": { "limits": [{
+                "type": "Container",
+                "default": { "memory": "512Mi" },
+                "defaultRequest": { "memory": "256Mi" },
+            }] },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            format_quota(&[quota], &[range]),
+            "QUOTA     RESOURCE       USED   HARD\n\
+             compute   pods           3      10\n\
+             compute   requests.cpu   0      4\n\
+             \n\
+             LIMITRANGE   TYPE        RESOURCE   MIN   MAX   DEFAULT REQUEST   DEFAULT LIMIT\n\
+             defaults     Container   memory     -     -     256Mi             512Mi\n"
+        );
+    }
+
+    #[test]
+    fn applied_diff_shows_drifted_fields() {
+        let applied = json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": "web", "namespace": "default", "labels": { "app": "web" } },
+            "spec": { "replicas": 3 },
+        });
+
+        let diff = format_applied_diff(&deployment(5, Some(applied))).unwrap();
+
+        assert_eq!(
+            diff,
+            "--- last-applied\n+++ live\n\
+             @@ .metadata.labels @@\n-{\"app\":\"web\"}\n\
+             @@ .spec.replicas @@\n-3\n+5\n"
+        );
+    }
+
+    #[test]
+    fn applied_diff_is_empty_without_drift() {
+        let applied = json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": "web", "namespace": "default" },
+            "spec": { "replicas": 3 },
+        });
+
+        assert_eq!(
+            format_applied_diff(&deployment(3, Some(applied))).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn applied_diff_explains_a_missing_annotation() {
+        let diff = format_applied_diff(&deployment(3, None)).unwrap();
+
+        assert!(diff.starts_with("web has no kubectl.kubernetes.io/last-applied-configuration"));
     }
 }